@@ -0,0 +1,52 @@
+#![feature(test)]
+
+extern crate iridium;
+extern crate test;
+
+use test::Bencher;
+
+use iridium::core::{Block, BlockHeader, ChainSpec, PowAlgorithm};
+use iridium::core::params::DEFAULT_MEMORY_BLOCKS;
+
+// A single proof attempt for every algorithm. Mining takes roughly 2^difficulty of these, so the
+// ratios show how much lower the difficulty of a memory-hard chain has to be for the same block
+// time, and how much less a faster hash function alone helps with it
+
+fn header() -> BlockHeader {
+    let mut block = Block::template(&[Block::genesis(&ChainSpec::default())], vec![]);
+    block.header.proof = 12345;
+    block.header
+}
+
+fn attempt(bencher: &mut Bencher, algorithm: PowAlgorithm) {
+    let mut header = header();
+    bencher.iter(|| {
+        header.proof += 1;
+        header.pow_hash(algorithm)
+    });
+}
+
+#[bench]
+fn sha512(bencher: &mut Bencher) {
+    attempt(bencher, PowAlgorithm::Sha512);
+}
+
+#[bench]
+fn memory_hard_small(bencher: &mut Bencher) {
+    attempt(bencher, PowAlgorithm::MemoryHard { blocks: 64 });
+}
+
+#[bench]
+fn memory_hard_default(bencher: &mut Bencher) {
+    attempt(
+        bencher,
+        PowAlgorithm::MemoryHard {
+            blocks: DEFAULT_MEMORY_BLOCKS,
+        },
+    );
+}
+
+#[bench]
+fn memory_hard_large(bencher: &mut Bencher) {
+    attempt(bencher, PowAlgorithm::MemoryHard { blocks: 16384 });
+}
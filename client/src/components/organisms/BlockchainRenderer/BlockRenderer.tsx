@@ -1,12 +1,12 @@
 import React from "react";
 import { Button, Collapse, Input, Form, Card } from "antd";
 
-import { Block, Client, hashBlock } from "data/blockchain";
+import { Block, BlockHeader, Client, hashBlock, hashHeader } from "data/blockchain";
 
 import "./BlockRenderer.scss";
 import { Dict } from "data/common/types";
 import TransactionRenderer from "components/organisms/BlockchainRenderer/TransactionRenderer";
-import { hash } from "core/crypto";
+import { meetsDifficulty } from "core/crypto";
 
 interface Props {
   previousBlock?: Block;
@@ -34,30 +34,34 @@ export default class BlockRenderer extends React.PureComponent<Props, BlockState
     this.state = {};
   }
 
-  makeTampered() {
-    const { block } = this.props;
+  makeTampered(): BlockHeader {
+    const { header } = this.props.block;
 
     const { index, previous_hash, timestamp, proof } = this.state;
     return {
-      index: index != null ? index : block.index,
-      timestamp: timestamp || block.timestamp,
-      proof: proof || block.proof,
-      previous_hash: previous_hash || block.previous_hash,
-      transactions: block.transactions
+      index: index != null ? index : header.index,
+      timestamp: timestamp || header.timestamp,
+      difficulty: header.difficulty,
+      proof: proof || header.proof,
+      extra_nonce: header.extra_nonce,
+      previous_hash: previous_hash || header.previous_hash,
+      merkle_root: header.merkle_root,
+      signature: header.signature
     };
   }
 
   isValid() {
     const { block, previousBlock } = this.props;
-    if (block && this.state && block.index === 0) {
+    if (block && this.state && block.header.index === 0) {
       return true;
     }
 
-    const newHash = hash(this.makeTampered());
+    const { header } = block;
+    const newHash = hashHeader(this.makeTampered());
     return (
-      block.previous_hash === previousBlock!.hash &&
-      (previousBlock!.index === 0 || block.previous_hash === hashBlock(previousBlock!)) &&
-      newHash.startsWith("0000") &&
+      header.previous_hash === previousBlock!.hash &&
+      (previousBlock!.header.index === 0 || header.previous_hash === hashBlock(previousBlock!)) &&
+      meetsDifficulty(newHash, header.difficulty) &&
       block.hash === newHash
     );
   }
@@ -68,14 +72,20 @@ export default class BlockRenderer extends React.PureComponent<Props, BlockState
       <Card className="block" style={{ borderColor: this.isValid() ? "inherit" : "red" }}>
         <Button
           style={{ width: "100%", marginBottom: "6px" }}
-          onClick={() => tamper({ ...this.makeTampered(), hash: block.hash })}
+          onClick={() =>
+            tamper({
+              header: this.makeTampered(),
+              hash: block.hash,
+              transactions: block.transactions
+            })
+          }
         >
           Tamper
         </Button>
         <Form>
           <Form.Item label="Index" {...formItemLayout}>
             <Input
-              defaultValue={block.index}
+              defaultValue={block.header.index}
               onChange={e => this.setState({ index: +e.target.value })}
             />
           </Form.Item>
@@ -83,23 +93,25 @@ export default class BlockRenderer extends React.PureComponent<Props, BlockState
             label="Prev. hash"
             {...formItemLayout}
             validateStatus={
-              !previousBlock || previousBlock.hash === block.previous_hash ? "success" : "error"
+              !previousBlock || previousBlock.hash === block.header.previous_hash
+                ? "success"
+                : "error"
             }
           >
             <Input
-              defaultValue={block.previous_hash}
+              defaultValue={block.header.previous_hash}
               onChange={e => this.setState({ previous_hash: e.target.value })}
             />
           </Form.Item>
           <Form.Item label="Timestamp" {...formItemLayout}>
             <Input
-              defaultValue={block.timestamp}
+              defaultValue={block.header.timestamp}
               onChange={e => this.setState({ timestamp: +e.target.value })}
             />
           </Form.Item>
           <Form.Item label="Proof" {...formItemLayout}>
             <Input
-              defaultValue={block.proof}
+              defaultValue={block.header.proof}
               onChange={e => this.setState({ proof: +e.target.value })}
             />
           </Form.Item>
@@ -2,7 +2,7 @@ import React from "react";
 import { Icon } from "antd";
 
 import { getPublicKey } from "core/crypto";
-import { Transaction, Client, isReward } from "data/blockchain";
+import { Transaction, Client } from "data/blockchain";
 import { Dict } from "data/common/types";
 
 import "./TransactionRenderer.scss";
@@ -14,31 +14,49 @@ interface Props {
 
 export default class TransactionRenderer extends React.PureComponent<Props> {
   render() {
-    const { transaction } = this.props;
+    return <div>{this.renderTransaction(this.props.transaction)}</div>;
+  }
 
-    return (
-      <div>
-        {isReward(transaction) ? (
-          <>
-            <div className="tx-reward">
-              <span>Coinbase</span>
-              <Icon type="arrow-right" />
-              <span className="text-right">{this.mapName(transaction.recipient)}</span>
-              <span className="text-right">${transaction.amount}</span>
-            </div>
-          </>
-        ) : (
-          <>
-            <div className="tx-transfer">
-              <span>{this.mapName(transaction.sender)}</span>
-              <Icon type="arrow-right" />
-              <span className="text-right">{this.mapName(transaction.recipient)}</span>
-              <span className="text-right">${transaction.amount}</span>
-            </div>
-          </>
-        )}
-      </div>
-    );
+  renderTransaction(transaction: Transaction) {
+    switch (transaction.type) {
+      case "Reward":
+        return (
+          <div className="tx-reward">
+            <span>Coinbase</span>
+            <Icon type="arrow-right" />
+            <span className="text-right">{this.mapName(transaction.recipient)}</span>
+            <span className="text-right">${transaction.amount}</span>
+          </div>
+        );
+      case "Transfer":
+        return (
+          <div className="tx-transfer">
+            <span>{this.mapName(transaction.sender)}</span>
+            <Icon type="arrow-right" />
+            <span className="text-right">{this.mapName(transaction.recipient)}</span>
+            <span className="text-right" title={`Fee: $${transaction.fee}`}>
+              ${transaction.amount}
+            </span>
+          </div>
+        );
+      case "Stake":
+        return (
+          <div className="tx-stake">
+            <span>{this.mapName(transaction.validator)}</span>
+            <Icon type={transaction.action === "Lock" ? "lock" : "unlock"} />
+            <span className="text-right">{transaction.action}</span>
+            <span className="text-right">${transaction.amount}</span>
+          </div>
+        );
+      case "Slash":
+        return (
+          <div className="tx-slash">
+            <span>{this.mapName(transaction.validator)}</span>
+            <Icon type="close-circle" />
+            <span className="text-right">Slashed at #{transaction.first.index}</span>
+          </div>
+        );
+    }
   }
 
   mapName(publicKey: string): string {
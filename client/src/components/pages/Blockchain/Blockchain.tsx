@@ -34,7 +34,7 @@ interface DispatchProps {
   broadcast(): void;
   sendCoins(): void;
   tamper(block: Block): void;
-  makeTransaction(to: string, amount: number, from?: string): void;
+  makeTransaction(to: string, amount: number, fee: number, from?: string): void;
   registerClient(name: string, port: number): void;
 }
 
@@ -45,6 +45,7 @@ interface State {
   transactionSender?: string;
   transactionRecipient?: string;
   transactionAmount?: number;
+  transactionFee?: number;
 }
 
 export class Blockchain extends React.PureComponent<StoreProps & DispatchProps, State> {
@@ -78,6 +79,7 @@ export class Blockchain extends React.PureComponent<StoreProps & DispatchProps,
       clientPort,
       clientName,
       transactionAmount,
+      transactionFee,
       transactionRecipient,
       transactionSender
     } = this.state;
@@ -122,11 +124,22 @@ export class Blockchain extends React.PureComponent<StoreProps & DispatchProps,
             placeholder="Amount"
             onChange={e => this.setState({ transactionAmount: +e! })}
           />
+          <InputNumber
+            className="input-fee"
+            placeholder="Fee"
+            min={0}
+            onChange={e => this.setState({ transactionFee: +e! })}
+          />
           <Button
             className="send-transaction"
             type="primary"
             onClick={() =>
-              makeTransaction(transactionRecipient!, transactionAmount!, transactionSender)
+              makeTransaction(
+                transactionRecipient!,
+                transactionAmount!,
+                transactionFee || 0,
+                transactionSender
+              )
             }
           >
             Send
@@ -2,11 +2,12 @@ import React from "react";
 import { bindActionCreators } from "redux";
 import { connect, Dispatch } from "react-redux";
 import { DragDropContext, Draggable, Droppable, DropResult } from "react-beautiful-dnd";
-import { Badge, Button, Icon, Divider } from "antd";
+import { Badge, Button, Icon, Divider, Radio } from "antd";
+import { RadioChangeEvent } from "antd/lib/radio";
 
 import { repeat } from "core/util";
 import { AppState } from "data";
-import { start, pause, reset, setAttackers } from "data/proof-of-work";
+import { start, pause, reset, setAttackers, setAlgorithm, PowAlgorithm } from "data/proof-of-work";
 
 import "./Visualizations.scss";
 
@@ -16,6 +17,7 @@ interface Node {
 }
 
 interface StoreProps {
+  algorithm: PowAlgorithm;
   attackers: number;
   users: number;
   nodes: Node[];
@@ -29,6 +31,7 @@ interface DispatchProps {
   pause(): void;
   reset(): void;
   setAttackers(attackers: number): void;
+  setAlgorithm(algorithm: PowAlgorithm): void;
 }
 
 const getItemStyle = (isDragging: any, draggableStyle: any) => ({
@@ -46,7 +49,7 @@ const FixedDraggable = Draggable as any;
 
 export class Visualizations extends React.PureComponent<StoreProps & DispatchProps> {
   render() {
-    const { nodes, attackers, users } = this.props;
+    const { nodes, attackers, users, algorithm } = this.props;
     return (
       <div className="visualizations">
         <div className="controls">
@@ -59,6 +62,10 @@ export class Visualizations extends React.PureComponent<StoreProps & DispatchPro
           <Button onClick={this.reset} className="simulation-control" type="danger">
             Reset
           </Button>
+          <Radio.Group value={algorithm} onChange={this.setAlgorithm}>
+            <Radio.Button value="Sha512">SHA-512</Radio.Button>
+            <Radio.Button value="MemoryHard">Memory-hard</Radio.Button>
+          </Radio.Group>
         </div>
         <DragDropContext onDragEnd={this.onDragEnd}>
           <Droppable droppableId="droppable">
@@ -122,6 +129,10 @@ export class Visualizations extends React.PureComponent<StoreProps & DispatchPro
     this.props.reset();
   };
 
+  setAlgorithm = (e: RadioChangeEvent) => {
+    this.props.setAlgorithm(e.target.value);
+  };
+
   renderProgress(node: Node) {
     const { validProgress, attackerProgress } = this.props;
     const elements = node.type === "attacker" ? attackerProgress : validProgress;
@@ -144,6 +155,7 @@ export class Visualizations extends React.PureComponent<StoreProps & DispatchPro
 export default connect<StoreProps, DispatchProps>(
   ({ proofOfWork }: AppState) => ({
     target: proofOfWork.target,
+    algorithm: proofOfWork.algorithm,
     attackers: proofOfWork.attackers,
     users: proofOfWork.users,
     nodes: [
@@ -157,5 +169,6 @@ export default connect<StoreProps, DispatchProps>(
     validProgress: proofOfWork.validProgress,
     attackerProgress: proofOfWork.attackerProgress
   }),
-  (dispatch: Dispatch<any>) => bindActionCreators({ setAttackers, start, pause, reset }, dispatch)
+  (dispatch: Dispatch<any>) =>
+    bindActionCreators({ setAttackers, setAlgorithm, start, pause, reset }, dispatch)
 )(Visualizations);
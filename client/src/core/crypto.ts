@@ -1,5 +1,4 @@
 import * as nacl from "tweetnacl";
-import { TextEncoder } from "text-encoding";
 
 let keyPair: nacl.SignKeyPair;
 keyPair =
@@ -15,8 +14,6 @@ keyPair =
     }
   })();
 
-const encoder = new TextEncoder();
-
 export function byteToHexString(uint8arr: Uint8Array): string {
   if (!uint8arr) {
     return "";
@@ -49,12 +46,30 @@ export function getPublicKey(): string {
   return byteToHexString(keyPair.publicKey);
 }
 
-export function sign<T>(value: T): string {
-  const messageBytes = encoder.encode(JSON.stringify(value));
-  return byteToHexString(nacl.sign.detached(messageBytes, keyPair.secretKey));
+// Messages have to be in the canonical encoding, see core/encoding
+export function sign(message: Uint8Array): string {
+  return byteToHexString(nacl.sign.detached(message, keyPair.secretKey));
 }
 
-export function hash<T>(value: T): string {
-  const message = encoder.encode(JSON.stringify(value));
+export function hash(message: Uint8Array): string {
   return byteToHexString(nacl.hash(message));
 }
+
+// Counts the leading zero bits of a hex-encoded hash, the same way the node does
+export function leadingZeroBits(hash: string): number {
+  let bits = 0;
+  for (const char of hash) {
+    const digit = parseInt(char, 16);
+    if (digit === 0) {
+      bits += 4;
+    } else {
+      bits += Math.clz32(digit) - 28;
+      break;
+    }
+  }
+  return bits;
+}
+
+export function meetsDifficulty(hash: string, difficulty: number): boolean {
+  return leadingZeroBits(hash) >= difficulty;
+}
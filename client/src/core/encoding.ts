@@ -0,0 +1,105 @@
+import { TextEncoder } from "text-encoding";
+
+import { hexStringToByte } from "core/crypto";
+
+// Must match the node's encoding version, see ENCODING.md in the repository root
+export const ENCODING_VERSION = 5;
+
+const textEncoder = new TextEncoder();
+const U32_RANGE = 0x100000000;
+
+// Writes values in the node's canonical binary encoding, used for hashing and signing
+export class Encoder {
+  private readonly bytes: number[] = [ENCODING_VERSION];
+
+  u32(value: number): this {
+    for (let i = 0; i < 4; i++) {
+      this.bytes.push((value >>> (8 * i)) & 0xff);
+    }
+    return this;
+  }
+
+  // Only exact for values that fit into a double without losing precision
+  i64(value: number): this {
+    const high = Math.floor(value / U32_RANGE);
+    return this.u32(value - high * U32_RANGE).u32(high >>> 0);
+  }
+
+  u64(value: number): this {
+    return this.i64(value);
+  }
+
+  raw(bytes: Uint8Array): this {
+    bytes.forEach(byte => this.bytes.push(byte));
+    return this;
+  }
+
+  string(value: string): this {
+    const bytes = textEncoder.encode(value);
+    return this.u64(bytes.length).raw(bytes);
+  }
+
+  option<T>(value: T | null | undefined, write: (encoder: this, value: T) => void): this {
+    if (value == null) {
+      this.bytes.push(0);
+    } else {
+      this.bytes.push(1);
+      write(this, value);
+    }
+    return this;
+  }
+
+  uuid(value: string): this {
+    return this.raw(hexStringToByte(value.replace(/-/g, "")));
+  }
+
+  finish(): Uint8Array {
+    return new Uint8Array(this.bytes);
+  }
+}
+
+export interface TransferMessage {
+  readonly id: string;
+  readonly sender: string;
+  readonly recipient: string;
+  readonly amount: number;
+  readonly fee: number;
+  readonly nonce: number;
+  readonly valid_after?: number | null;
+  readonly valid_until?: number | null;
+}
+
+export interface HeaderMessage {
+  readonly index: number;
+  readonly timestamp: number;
+  readonly difficulty: number;
+  readonly proof: number;
+  readonly extra_nonce: number;
+  readonly previous_hash?: string | null;
+  readonly merkle_root: string;
+}
+
+export function encodeTransfer(transfer: TransferMessage): Uint8Array {
+  return new Encoder()
+    .uuid(transfer.id)
+    .string(transfer.sender)
+    .string(transfer.recipient)
+    .i64(transfer.amount)
+    .i64(transfer.fee)
+    .u64(transfer.nonce)
+    .option(transfer.valid_after, (encoder, height) => encoder.u64(height))
+    .option(transfer.valid_until, (encoder, height) => encoder.u64(height))
+    .finish();
+}
+
+export function encodeHeader(header: HeaderMessage): Uint8Array {
+  return new Encoder()
+    .u64(header.index)
+    .i64(header.timestamp)
+    .u32(header.difficulty)
+    .u64(header.proof)
+    .u64(header.extra_nonce)
+    .option(header.previous_hash, (encoder, hash) => encoder.string(hash))
+    .string(header.merkle_root)
+    .finish();
+}
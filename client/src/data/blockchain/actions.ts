@@ -61,6 +61,7 @@ export interface MakeTransaction extends TypedAction<ActionTypes.MakeTransaction
   readonly from?: string;
   readonly to: string;
   readonly amount: number;
+  readonly fee: number;
 }
 
 export type Action =
@@ -128,9 +129,15 @@ export const setError = (error: BlockError): Action => ({
   error
 });
 
-export const makeTransaction = (to: string, amount: number, from?: string): Action => ({
+export const makeTransaction = (
+  to: string,
+  amount: number,
+  fee: number,
+  from?: string
+): Action => ({
   type: ActionTypes.MakeTransaction,
   to,
   from,
-  amount
+  amount,
+  fee
 });
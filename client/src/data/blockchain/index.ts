@@ -15,8 +15,9 @@ import {
   setOwnBlockchain,
   setError
 } from "./actions";
-import { State, MinerInformation, Blockchain } from "./model";
+import { State, MinerInformation, Blockchain, nextNonce } from "./model";
 import { getPublicKey, sign, byteToHexString, hexStringToByte } from "core/crypto";
+import { encodeTransfer } from "core/encoding";
 
 const initialState: State = {
   clients: {}
@@ -144,16 +145,19 @@ export function epic(
             })
             .map(event => setBlockchain(firstClient.public_key, event.response));
         case ActionTypes.MakeTransaction:
+          const sender = action.from || getPublicKey();
           const message = {
             id: uuid.v4(),
-            sender: action.from || getPublicKey(),
+            sender,
             recipient: action.to,
-            amount: action.amount
+            amount: action.amount,
+            fee: action.fee,
+            nonce: nextNonce(blockchain.ownBlockchain || firstClient.blockchain, sender)
           };
           return Observable.ajax
             .post(
               `http://localhost:${firstClient.port}/transactions/new`,
-              { ...message, signature: sign(message) },
+              { ...message, signature: sign(encodeTransfer(message)) },
               { "Content-Type": "application/json" }
             )
             .map(event => handleError(event) || setOwnBlockchain(event.response));
@@ -1,5 +1,6 @@
 import { Dict } from "core/dict";
 import { hash } from "core/crypto";
+import { encodeHeader } from "core/encoding";
 
 export interface Reward {
   readonly type: "Reward";
@@ -12,19 +13,48 @@ export interface Transfer {
   readonly type: "Transfer";
   readonly id: string;
   readonly amount: number;
+  readonly fee: number;
+  readonly nonce: number;
+  readonly valid_after: number | null;
+  readonly valid_until: number | null;
   readonly recipient: string;
   readonly sender: string;
   readonly signature: string;
 }
 
-export type Transaction = Reward | Transfer;
+export interface Stake {
+  readonly type: "Stake";
+  readonly id: string;
+  readonly validator: string;
+  readonly action: "Lock" | "Unlock";
+  readonly amount: number;
+  readonly signature: string;
+}
 
-export interface Block {
+export interface Slash {
+  readonly type: "Slash";
+  readonly id: string;
+  readonly validator: string;
+  readonly first: BlockHeader;
+  readonly second: BlockHeader;
+}
+
+export type Transaction = Reward | Transfer | Stake | Slash;
+
+export interface BlockHeader {
   readonly index: number;
   readonly timestamp: number;
+  readonly difficulty: number;
   readonly proof: number;
-  readonly hash: string;
+  readonly extra_nonce: number;
   readonly previous_hash: string;
+  readonly merkle_root: string;
+  readonly signature?: string | null;
+}
+
+export interface Block {
+  readonly header: BlockHeader;
+  readonly hash: string;
   readonly transactions: Transaction[];
 }
 
@@ -60,6 +90,23 @@ export function isReward(tx: Transaction): tx is Reward {
   return tx.type === "Reward";
 }
 
-export function hashBlock({ index, timestamp, proof, previous_hash, transactions }: Block): string {
-  return hash({ index, timestamp, proof, previous_hash, transactions });
+export function isTransfer(tx: Transaction): tx is Transfer {
+  return tx.type === "Transfer";
+}
+
+// The nonce of the sender's next transfer: one more than its last mined or pending transfer
+export function nextNonce(blockchain: Blockchain, sender: string): number {
+  const transactions = [
+    ...blockchain.blocks.reduce<Transaction[]>((all, block) => all.concat(block.transactions), []),
+    ...blockchain.transactions
+  ];
+  return transactions.filter(tx => isTransfer(tx) && tx.sender === sender).length;
+}
+
+export function hashHeader(header: BlockHeader): string {
+  return hash(encodeHeader(header));
+}
+
+export function hashBlock(block: Block): string {
+  return hashHeader(block.header);
 }
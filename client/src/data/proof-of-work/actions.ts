@@ -1,11 +1,14 @@
 import { TypedAction } from "data/common/types";
 
+import { PowAlgorithm } from "./model";
+
 export const enum ActionTypes {
   Start = "proof-of-work/Start",
   Pause = "proof-of-work/Pause",
   Reset = "proof-of-work/Reset",
   SetTarget = "proof-of-work/SetTarget",
   SetStepSize = "proof-of-work/SetStepSize",
+  SetAlgorithm = "proof-of-work/SetAlgorithm",
   SetAttackers = "proof-of-work/SetAttackers",
   IncrementAttackerProgress = "proof-of-work/IncrementAttackerProgress",
   SetUsers = "proof-of-work/SetUsers",
@@ -24,6 +27,10 @@ export interface SetStepSize extends TypedAction<ActionTypes.SetStepSize> {
   readonly stepSize: number;
 }
 
+export interface SetAlgorithm extends TypedAction<ActionTypes.SetAlgorithm> {
+  readonly algorithm: PowAlgorithm;
+}
+
 export interface SetAttackers extends TypedAction<ActionTypes.SetAttackers> {
   readonly attackers: number;
 }
@@ -59,6 +66,11 @@ export const setStepSize = (stepSize: number): Action => ({
   stepSize
 });
 
+export const setAlgorithm = (algorithm: PowAlgorithm): Action => ({
+  type: ActionTypes.SetAlgorithm,
+  algorithm
+});
+
 export const setAttackers = (attackers: number): Action => ({
   type: ActionTypes.SetAttackers,
   attackers
@@ -83,6 +95,7 @@ export type Action =
   | Reset
   | SetTarget
   | SetStepSize
+  | SetAlgorithm
   | SetAttackers
   | IncrementAttackerProgress
   | SetUsers
@@ -3,13 +3,14 @@ import { ActionsObservable } from "redux-observable";
 import { Observable } from "rxjs/Rx";
 
 import { Action, ActionTypes, incrementAttackerProgress, incrementValidProgress } from "./actions";
-import { State } from "./model";
+import { State, HARDWARE_SPEEDUP } from "./model";
 import { AppState } from "data";
 
 const initialState: State = {
   isRunning: false,
   target: 130,
   stepSize: 1,
+  algorithm: "Sha512",
   attackers: 0,
   users: 18,
   attackerProgress: 20,
@@ -20,6 +21,8 @@ export function reducer(state = initialState, action: Action): State {
   switch (action.type) {
     case ActionTypes.SetTarget:
       return { ...state, target: action.target };
+    case ActionTypes.SetAlgorithm:
+      return { ...state, algorithm: action.algorithm };
     case ActionTypes.SetAttackers:
       const attackerProgress = state.attackers === 0 ? state.validProgress : state.attackerProgress;
       return { ...state, attackers: action.attackers, attackerProgress };
@@ -39,7 +42,12 @@ export function epic(
   store: MiddlewareAPI<AppState>
 ): Observable<any> {
   return actions$
-    .ofType(ActionTypes.Start, ActionTypes.SetAttackers, ActionTypes.SetUsers)
+    .ofType(
+      ActionTypes.Start,
+      ActionTypes.SetAttackers,
+      ActionTypes.SetUsers,
+      ActionTypes.SetAlgorithm
+    )
     .switchMap((action: Action) => {
       const { proofOfWork } = store.getState();
       if (!proofOfWork.isRunning && action.type !== ActionTypes.Start) {
@@ -54,12 +62,13 @@ export function epic(
       }
 
       const result = [];
-      const { users, attackers } = proofOfWork;
+      const { users, attackers, algorithm } = proofOfWork;
       const valid = users - attackers;
 
       if (attackers > 0) {
+        const attackerPower = attackers * HARDWARE_SPEEDUP[algorithm];
         result.push(
-          Observable.timer(0, Math.floor(500 * valid / attackers)).map(() =>
+          Observable.timer(0, Math.floor(500 * valid / attackerPower)).map(() =>
             incrementAttackerProgress()
           )
         );
@@ -1,7 +1,19 @@
+// The proof of work hash functions a chain can use, see `PowAlgorithm` in the node
+export type PowAlgorithm = "Sha512" | "MemoryHard";
+
+// How much faster the adversaries' specialised hardware mines than a regular computer. Plain
+// SHA-512 is easy to implement in silicon, while the memory-hard hash is mostly limited by memory
+// access, which custom hardware can't speed up nearly as much. Rough figures for illustration
+export const HARDWARE_SPEEDUP: Record<PowAlgorithm, number> = {
+  Sha512: 8,
+  MemoryHard: 1.5
+};
+
 export interface State {
   readonly isRunning: boolean;
   readonly target: number;
   readonly stepSize: number;
+  readonly algorithm: PowAlgorithm;
 
   readonly users: number;
   readonly attackers: number;
@@ -1,87 +1,150 @@
+use std::cmp;
+
 use chrono;
-use rand::{self, Rng};
+use uuid::Uuid;
 
-use super::Verify;
 use super::{BlockErrorKind, BlockchainError};
+use super::consensus::Consensus;
+use super::header::{self, BlockHeader};
+use super::index::ChainIndex;
+use super::merkle::{self, MerkleProof};
+use super::spec::ChainSpec;
 use super::transaction::Transaction;
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Block {
-    pub index: u64,
-    pub timestamp: i64,
-    pub proof: u32,
+    pub header: BlockHeader,
     pub hash: String,
-    pub previous_hash: Option<String>,
     pub transactions: Vec<Transaction>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct VerifiedBlock<'a> {
-    pub index: u64,
-    pub timestamp: i64,
-    pub proof: u32,
-    pub previous_hash: Option<&'a str>,
-    pub transactions: &'a [Transaction],
-}
-
 impl Block {
     // The first block of the blockchain. It's a special case because it doesn't have a valid
-    // proof or hash (to avoid the need to recompute these whenever a blockchain is validated)
-    pub fn genesis() -> Self {
+    // proof: it's derived from the chain spec instead, and its hash is the hash of the spec. Its
+    // only transactions are the spec's allocations
+    pub fn genesis(spec: &ChainSpec) -> Self {
+        let transactions = spec.genesis_transactions();
         Self {
-            index: 0,
-            timestamp: 0,
-            transactions: vec![],
-            proof: 0,
-            hash: String::new(),
-            previous_hash: None,
+            header: BlockHeader {
+                index: 0,
+                timestamp: spec.genesis_timestamp,
+                difficulty: 0,
+                proof: 0,
+                extra_nonce: 0,
+                previous_hash: None,
+                merkle_root: merkle::merkle_root(&transaction_hashes(&transactions)),
+                signature: None,
+            },
+            hash: spec.hash(),
+            transactions,
         }
     }
 
-    // Creates the next block in the chain with the given list of transactions. This also calculates
-    // the valid proof and hash
-    pub fn next(previous: &Block, transactions: Vec<Transaction>) -> Self {
-        let mut block = Self {
-            index: previous.index + 1,
-            timestamp: chrono::Utc::now().timestamp(),
-            transactions,
-            proof: 0,
+    // Creates the block following `chain` with the given list of transactions. The block still
+    // needs to be prepared and sealed by the consensus engine before it can be added to the chain.
+    // Its timestamp is the current time, unless that isn't later than the recent blocks allow
+    pub fn template(chain: &[Block], transactions: Vec<Transaction>) -> Self {
+        let previous = chain.last().expect("Zero-length chains are invalid");
+        let timestamp = cmp::max(
+            chrono::Utc::now().timestamp(),
+            header::median_time_past(chain) + 1,
+        );
+        Self {
+            header: BlockHeader {
+                index: previous.index() + 1,
+                timestamp,
+                difficulty: 0,
+                proof: 0,
+                extra_nonce: 0,
+                previous_hash: Some(previous.hash.clone()),
+                merkle_root: merkle::merkle_root(&transaction_hashes(&transactions)),
+                signature: None,
+            },
             hash: String::new(),
-            previous_hash: Some(previous.hash.clone()),
-        };
-        block.find_proof();
-        block
+            transactions,
+        }
     }
 
-    pub fn validate(&self, previous: &Block) -> Result<(), BlockchainError> {
-        self.validate_hashes(previous)
-            .and_then(|_| self.validate_transactions())
+    pub fn index(&self) -> u64 {
+        self.header.index
     }
 
-    // Verifies that:
-    // 1. the cached hash matches the block's actual hash
-    // 2. the previous hash matches the previous block's hash
-    fn validate_hashes(&self, previous: &Block) -> Result<(), BlockchainError> {
-        self.try_hash().and_then(|actual_hash| {
-            if Some(&previous.hash) != self.previous_hash.as_ref() {
-                Err(BlockchainError::block(
-                    self.index,
-                    BlockErrorKind::PreviousHashMismatch,
-                ))
-            } else if actual_hash != self.hash {
-                Err(BlockchainError::block(
-                    self.index,
-                    BlockErrorKind::HashMismatch,
-                ))
-            } else {
-                Ok(())
-            }
-        })
+    // Builds a proof that the transaction with the given id is part of this block, which can be
+    // checked against the block's Merkle root alone
+    pub fn merkle_proof(&self, id: &Uuid) -> Option<MerkleProof> {
+        self.transactions
+            .iter()
+            .position(|tx| tx.id() == id)
+            .and_then(|index| MerkleProof::build(&transaction_hashes(&self.transactions), index))
+    }
+
+    // Validates the block as the one following `chain`, which has to contain at least the
+    // genesis block. `index` is the state `chain` leaves behind
+    pub fn validate<C: Consensus>(
+        &self,
+        chain: &[Block],
+        index: &ChainIndex,
+        consensus: &C,
+        spec: &ChainSpec,
+    ) -> Result<(), BlockchainError> {
+        self.validate_structure(chain, index, consensus, spec)
+            .and_then(|_| self.validate_transactions(spec, |_, tx| tx.validate()))
+    }
+
+    // The cheap part of `validate`: the header, the seal, the Merkle root and the number of
+    // transactions. None of it looks at the transactions' signatures, so a chain can be checked
+    // this way before any of the (slow) signature checks are spent on it
+    pub fn validate_structure<C: Consensus>(
+        &self,
+        chain: &[Block],
+        index: &ChainIndex,
+        consensus: &C,
+        spec: &ChainSpec,
+    ) -> Result<(), BlockchainError> {
+        self.validate_header(chain, index, consensus)
+            .and_then(|_| self.validate_merkle_root())
+            .and_then(|_| self.validate_transaction_count(spec))
     }
 
-    // Checks if all transactions in this block are valid
-    fn validate_transactions(&self) -> Result<(), BlockchainError> {
-        // First verify that there's only a single miner reward
+    // Checks the header against the previous one, lets the consensus engine verify the seal and
+    // verifies that the cached hash matches the block's actual hash
+    fn validate_header<C: Consensus>(
+        &self,
+        chain: &[Block],
+        index: &ChainIndex,
+        consensus: &C,
+    ) -> Result<(), BlockchainError> {
+        let previous = chain.last().expect("Zero-length chains are invalid");
+        self.header
+            .validate(chain, &previous.hash)
+            .and_then(|_| consensus.verify_seal(chain, index, &self.header))
+            .and_then(|actual_hash| {
+                if actual_hash != self.hash {
+                    Err(BlockchainError::block(
+                        self.index(),
+                        BlockErrorKind::HashMismatch,
+                    ))
+                } else {
+                    Ok(())
+                }
+            })
+    }
+
+    // The seal only covers the Merkle root, so it has to match the transactions
+    fn validate_merkle_root(&self) -> Result<(), BlockchainError> {
+        if merkle::merkle_root(&transaction_hashes(&self.transactions)) != self.header.merkle_root {
+            Err(BlockchainError::block(
+                self.index(),
+                BlockErrorKind::MerkleRootMismatch,
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    // Verifies that there's only a single miner reward and no more transactions than the spec
+    // allows
+    fn validate_transaction_count(&self, spec: &ChainSpec) -> Result<(), BlockchainError> {
         let reward_count = self.transactions
             .iter()
             .filter(|tx| match **tx {
@@ -92,54 +155,56 @@ impl Block {
 
         if reward_count != 1 {
             return Err(BlockchainError::block(
-                self.index,
+                self.index(),
                 BlockErrorKind::InvalidRewardCount,
             ));
         }
 
-        // Then verify each transaction separately
-        for transaction in &self.transactions {
-            transaction.validate()?;
+        if self.transactions.len() - reward_count > spec.max_block_transactions {
+            return Err(BlockchainError::block(
+                self.index(),
+                BlockErrorKind::TooManyTransactions,
+            ));
         }
-
         Ok(())
     }
 
-    // Calculates and returns the hash of this block if it is valid or None otherwise
-    fn try_hash(&self) -> Result<String, BlockchainError> {
-        let hash = self.calculate_hash::<VerifiedBlock>();
-        if hash.starts_with("0000") {
-            Ok(hash)
-        } else {
-            Err(BlockchainError::block(
-                self.index,
-                BlockErrorKind::InvalidProof,
-            ))
-        }
-    }
+    // The rest of `validate` once `validate_structure` passed: checks if all transactions in this
+    // block are valid. The checks of each transaction on its own are left to
+    // `validate_transaction`, which gets the transaction's position in the block. This lets the
+    // (slow) signature checks happen ahead of time
+    pub fn validate_transactions<F>(
+        &self,
+        spec: &ChainSpec,
+        mut validate_transaction: F,
+    ) -> Result<(), BlockchainError>
+    where
+        F: FnMut(usize, &Transaction) -> Result<(), BlockchainError>,
+    {
+        // The miner can claim the fees of every transaction in the block on top of the subsidy.
+        // Nothing has been verified yet, so the fees might add up to more than an i64 can hold
+        let total = spec.miner_reward(self.index(), &self.transactions)
+            .ok_or_else(|| BlockchainError::block(self.index(), BlockErrorKind::InvalidFees))?;
 
-    // Finds and saves the correct proof and hash for this node
-    fn find_proof(&mut self) {
-        // Start at a random value to avoid wasting work across multiple nodes
-        self.proof = rand::thread_rng().gen::<u32>();
-        loop {
-            if let Ok(hash) = self.try_hash() {
-                self.hash = hash;
-                break;
+        // Then verify each transaction separately, including whether it's valid at this height
+        for (i, transaction) in self.transactions.iter().enumerate() {
+            validate_transaction(i, transaction)?;
+            transaction.validate_height(self.index())?;
+            if let Transaction::Reward(ref reward) = *transaction {
+                reward.validate(total)?;
             }
-            self.proof = self.proof.wrapping_add(1);
         }
+
+        Ok(())
     }
 }
 
-impl<'a> From<&'a Block> for VerifiedBlock<'a> {
-    fn from(block: &Block) -> VerifiedBlock {
-        VerifiedBlock {
-            index: block.index,
-            timestamp: block.timestamp,
-            transactions: &block.transactions,
-            proof: block.proof,
-            previous_hash: block.previous_hash.as_ref().map(|x| x.as_ref()),
-        }
+impl AsRef<BlockHeader> for Block {
+    fn as_ref(&self) -> &BlockHeader {
+        &self.header
     }
 }
+
+fn transaction_hashes(transactions: &[Transaction]) -> Vec<String> {
+    transactions.iter().map(|tx| tx.hash()).collect()
+}
@@ -1,29 +1,86 @@
+use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
-use std::mem;
+use std::sync::Arc;
+
+use uuid::Uuid;
 
 use super::block::Block;
 use super::error::{BlockErrorKind, BlockchainError, ChainErrorKind, TransactionErrorKind};
-use super::transaction::{Transaction, Transfer};
-
-const PENDING_TRANSACTION_LIMIT: usize = 4;
+use super::header::HeaderChain;
+use super::index::ChainIndex;
+use super::mempool::Mempool;
+use super::merkle::MerkleProof;
+use super::consensus::{Consensus, Engine, ProofOfWork};
+use super::miner::MiningJob;
+use super::params::ChainParams;
+use super::spec::ChainSpec;
+use super::transaction::Transaction;
+use super::tree::{BlockTree, MAX_FORK_DEPTH};
+use super::verifier::Verifier;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Blockchain {
+pub struct Blockchain<C: Consensus = ProofOfWork> {
     pub blocks: Vec<Block>,
 
-    #[serde(default)]
-    pub transactions: Vec<Transaction>,
+    // The pending transactions, shared as `transactions`
+    #[serde(default, rename = "transactions")]
+    pub mempool: Mempool,
+
+    // Chains received from other nodes are always checked against our own consensus rules, so
+    // these are never sent over the network
+    #[serde(skip)]
+    pub consensus: C,
+
+    // The spec the chain was started from, which defines its genesis block and block rules
+    #[serde(skip)]
+    pub spec: ChainSpec,
+
+    // Verifies the signatures when the whole chain is validated. How many threads it uses is a
+    // local setting, just like the miner's
+    #[serde(skip)]
+    pub verifier: Verifier,
+
+    // Competing branches and orphans are local knowledge, only the best chain is shared
+    #[serde(skip)]
+    tree: BlockTree,
+
+    // Derived from the best chain, it's rebuilt whenever the best chain changes
+    #[serde(skip)]
+    index: ChainIndex,
 }
 
-// Gets all transactions in the blockchain as one flat iterator
-macro_rules! transactions {
-    ($chain: ident) => { $chain.blocks.iter().flat_map(|x| x.transactions.iter()) };
-    (with_pending $chain: ident) => { transactions!($chain).chain($chain.transactions.iter()) };
+// The outcome of `Blockchain::add_block` for blocks that weren't rejected
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum BlockStatus {
+    // The block was appended to the best chain
+    Extended,
+    // The block completed a competing branch with more work, which became the best chain
+    Reorganized,
+    // The block was stored as part of a competing branch
+    ForkStored,
+    // The block's parent is unknown, it is kept until the parent arrives
+    Orphaned,
+    // We already had the block
+    Known,
 }
 
-impl Blockchain {
-    pub fn new() -> Self {
-        Blockchain::default()
+impl<C: Consensus> Blockchain<C> {
+    pub fn with_consensus(consensus: C) -> Self {
+        Blockchain::with_spec(ChainSpec::default(), consensus)
+    }
+
+    pub fn with_spec(spec: ChainSpec, consensus: C) -> Self {
+        let blocks = vec![Block::genesis(&spec)];
+        let index = ChainIndex::from_blocks(&blocks);
+        Self {
+            blocks,
+            mempool: Mempool::default(),
+            consensus,
+            spec,
+            verifier: Verifier::default(),
+            tree: BlockTree::new(),
+            index,
+        }
     }
 
     pub fn len(&self) -> usize {
@@ -38,125 +95,378 @@ impl Blockchain {
         self.blocks.last().expect("Zero-length chains are invalid")
     }
 
+    // The total weight of the chain's blocks according to the consensus engine (the work that
+    // went into mining them for proof of work). This is what decides which chain wins when two of
+    // them compete, not their length
+    pub fn total_weight(&self) -> u64 {
+        self.blocks.iter().fold(0, |total: u64, block| {
+            total.saturating_add(self.consensus.weight(&block.header))
+        })
+    }
+
+    // Checks whether `self` should be preferred over `other`: the chain with more weight wins.
+    // Ties are broken by picking the lower tip hash, so every node settles on the same chain
+    // regardless of which one it has seen first
+    pub fn is_better_than(&self, other: &Blockchain<C>) -> bool {
+        match self.total_weight().cmp(&other.total_weight()) {
+            Ordering::Greater => true,
+            Ordering::Less => false,
+            Ordering::Equal => self.last_block().hash < other.last_block().hash,
+        }
+    }
+
+    // Validates the whole chain: every block is checked in order against the state the blocks
+    // before it leave behind, so the error points at the first block that breaks a rule. Finally
+    // the state has to match the incrementally updated index. The headers and seals are checked
+    // first, since they are cheap, and only the blocks up to the first bad one have their
+    // signatures verified (up front, on several threads). The results of those are only looked
+    // at once the pass reaches their transactions
     pub fn validate(&self) -> Result<(), BlockchainError> {
-        // TODO(gustorn): We should probably only iterate the transaction list once during the
-        // validation process.
-        self.validate_genesis_block()
-            .and_then(|_| self.validate_blocks())
-            .and_then(|_| self.validate_all_duplicate_transactions())
-            .and_then(|_| self.validate_all_balances())
-    }
-
-    pub fn replace(&mut self, other: Blockchain) -> Result<&Blockchain, BlockchainError> {
-        // We shouldn't replace our chain if the other one is longer. If they're equal
-        // we hope that our version will win out in the end
-        if other.len() <= self.len() {
+        self.validate_genesis_block()?;
+
+        // The state is needed by engines that depend on it. It's built without validating the
+        // transactions yet, if one of them is invalid the pass below fails before it gets there
+        let mut end = self.blocks.len();
+        let mut invalid = Ok(());
+        let mut index = ChainIndex::from_blocks(&self.blocks[..1]);
+        for i in 1..self.blocks.len() {
+            let (chain, block) = (&self.blocks[..i], &self.blocks[i]);
+            invalid = block.validate_structure(chain, &index, &self.consensus, &self.spec);
+            if invalid.is_err() {
+                end = i;
+                break;
+            }
+            index.apply(block);
+        }
+
+        let validated = self.verifier.validate_transactions(&self.blocks[1..end]);
+        let mut index = ChainIndex::from_blocks(&self.blocks[..1]);
+        for i in 1..end {
+            let block = &self.blocks[i];
+            let results = &validated[i - 1];
+            block.validate_transactions(&self.spec, |j, _| results[j].clone())?;
+            validate_block_state(block, &index)?;
+            index.apply(block);
+        }
+        invalid?;
+
+        if index == self.index {
+            Ok(())
+        } else {
+            Err(BlockchainError::chain(ChainErrorKind::IndexMismatch))
+        }
+    }
+
+    pub fn replace(
+        &mut self,
+        mut other: Blockchain<C>,
+    ) -> Result<&Blockchain<C>, BlockchainError> {
+        // The other chain has to follow our rules, not the ones it was deserialized with
+        other.consensus = self.consensus.clone();
+        other.spec = self.spec.clone();
+        other.verifier = self.verifier.clone();
+        other.index = ChainIndex::from_blocks(&other.blocks);
+
+        // We only replace our chain if the other one has more weight behind it. The claimed
+        // weight is only trusted after validation, so this is just a cheap early exit
+        if !other.is_better_than(self) {
             return Ok(self);
         }
 
         other.validate().and_then(move |_| {
-            mem::replace(&mut self.blocks, other.blocks);
-
-            // TODO(gustorn): This is horribly inefficient but it's the easiest way to keep
-            // the relevant part of the pending transaction list
-            let pending_transactions = mem::replace(&mut self.transactions, vec![]);
-            for tx in pending_transactions {
-                if let Transaction::Transfer(transfer) = tx {
-                    let _ = self.new_transaction(transfer);
-                }
-            }
+            // Everything up to the first differing block is shared, the rest of the other chain
+            // is the branch we need to switch to
+            let shared = self.blocks
+                .iter()
+                .zip(other.blocks.iter())
+                .take_while(|&(ours, theirs)| ours.hash == theirs.hash)
+                .count();
+            let branch = other.blocks.split_off(shared);
+            self.reorganize(shared, branch);
             Ok(&*self)
         })
     }
 
-    pub fn new_transaction(&mut self, transfer: Transfer) -> Result<u64, BlockchainError> {
-        transfer
-            .validate()
-            .and_then(|_| self.validate_duplicate_transaction(&transfer))
-            .and_then(|_| self.validate_sender_balance(&transfer))
-            .and_then(|_| self.validate_pending_transactions())
-            .and_then(|_| {
-                self.transactions.push(Transaction::from(transfer));
-                Ok(self.last_block().index + 1)
-            })
+    // Adds a single block received from another node. Depending on where its parent is the block
+    // either extends the best chain, gets stored as part of a competing branch (switching over to
+    // that branch if it now has more work) or waits in the orphan pool until its parent arrives.
+    // Invalid blocks are rejected with the error that made them invalid
+    pub fn add_block(&mut self, block: Block) -> Result<BlockStatus, BlockchainError> {
+        if self.position(&block.hash).is_some() || self.tree.contains(&block.hash)
+            || self.tree.is_orphan(&block)
+        {
+            return Ok(BlockStatus::Known);
+        }
+
+        let parent = match block.header.previous_hash.clone() {
+            Some(parent) => parent,
+            None => {
+                return Err(BlockchainError::block(
+                    block.index(),
+                    BlockErrorKind::PreviousHashMismatch,
+                ))
+            }
+        };
+
+        let hash = block.hash.clone();
+        let status = if parent == self.last_block().hash {
+            // The common case: the block builds on our tip, so it's enough to check it against
+            // the current state instead of validating a whole candidate chain
+            self.validate_next_block(&block)?;
+            let len = self.len();
+            self.reorganize(len, vec![block]);
+            BlockStatus::Extended
+        } else if let Some((shared, mut branch)) = self.branch_to(&parent) {
+            branch.push(block);
+            self.connect_branch(shared, branch)?
+        } else {
+            // Orphans can't be validated without their parent, but the consensus engine checks
+            // that they took the work or the key a real block needs
+            let hash = self.consensus.verify_orphan(&self.blocks, &self.index, &block.header)?;
+            if hash != block.hash {
+                return Err(BlockchainError::block(
+                    block.index(),
+                    BlockErrorKind::HashMismatch,
+                ));
+            }
+            self.tree.add_orphan(block);
+            return Ok(BlockStatus::Orphaned);
+        };
+
+        // The new block might be the parent some orphans were waiting for. These were never
+        // requested by anyone directly, so invalid ones are simply dropped
+        for orphan in self.tree.take_orphans(&hash) {
+            let _ = self.add_block(orphan);
+        }
+        Ok(status)
+    }
+
+    // Adds a transaction to the mempool, which might evict cheaper transactions if it's full. A
+    // transfer with the nonce of a pending one replaces it if it pays enough to do so. Transfers
+    // have to be valid in the next block to be accepted. Rewards are only ever created by the
+    // miner of a block, so they can't be added this way
+    pub fn new_transaction<T: Into<Transaction>>(&mut self, tx: T) -> Result<u64, BlockchainError> {
+        let tx = tx.into();
+        tx.validate()
+            .and_then(|_| tx.validate_height(self.last_block().index() + 1))
+            .and_then(|_| self.validate_user_transaction(&tx))
+            .and_then(|_| self.validate_duplicate_transaction(&tx))
+            .and_then(|_| self.validate_nonce(&tx))
+            .and_then(|_| self.validate_sender_balance(&tx))
+            .and_then(|_| self.validate_pending_stakes(&tx))
+            .and_then(|_| self.mempool.insert(tx))
+            .map(|_| self.last_block().index() + 1)
     }
 
-    pub fn mine<M: AsRef<str>>(&mut self, miner: M) -> &Block {
-        let mut transactions = mem::replace(&mut self.transactions, vec![]);
-        transactions.insert(0, Transaction::reward(miner));
+    // Creates the block that should be mined next: the miner's reward, which includes the fees of
+    // the selected transactions, followed by the best paying pending transactions that fit in the
+    // block, built on top of the current tip. The chain isn't modified, so the (slow) sealing can
+    // happen without holding on to it. Fails if the consensus engine doesn't allow us to create
+    // the next block
+    pub fn block_template<M: AsRef<str>>(&self, miner: M) -> Result<Block, BlockchainError> {
+        let mut transactions = self.mempool
+            .select(self.spec.max_block_transactions, |selected, tx| {
+                self.fits_in_block(selected, tx)
+            });
+        let index = self.last_block().index() + 1;
+        let reward = self.spec
+            .miner_reward(index, &transactions)
+            .ok_or_else(|| BlockchainError::block(index, BlockErrorKind::InvalidFees))?;
+        transactions.insert(0, Transaction::reward(miner, reward));
+
+        let mut block = Block::template(&self.blocks, transactions);
+        self.consensus.prepare(&self.blocks, &self.index, &mut block.header)?;
+        Ok(block)
+    }
 
-        let block = Block::next(self.last_block(), transactions);
+    // Creates and seals the next block on top of the best chain. Fails if the consensus engine
+    // doesn't allow us to seal it
+    pub fn mine<M: AsRef<str>>(&mut self, miner: M) -> Result<&Block, BlockchainError> {
+        let template = self.block_template(miner)?;
+        let block = self.consensus.seal(template, &Arc::new(MiningJob::new()))?;
+
+        self.mempool.remove(&block.transactions);
+        self.index.apply(&block);
         self.blocks.push(block);
-        self.last_block()
+        self.purge_expired();
+        self.prune_tree();
+        Ok(self.last_block())
+    }
+
+    // The balance of the address in the best chain, without the pending transactions
+    pub fn balance(&self, address: &str) -> i64 {
+        self.index.balance(address)
+    }
+
+    // The nonce the sender's next transfer has to use, counting its pending transfers too
+    pub fn next_nonce(&self, sender: &str) -> u64 {
+        self.index.next_nonce(sender) + self.mempool.transfers_from(sender)
+    }
+
+    // Finds the block containing the transaction with the given id and proves its inclusion
+    pub fn transaction_proof(&self, id: &Uuid) -> Option<(&Block, MerkleProof)> {
+        self.blocks
+            .iter()
+            .filter_map(|block| block.merkle_proof(id).map(|proof| (block, proof)))
+            .next()
     }
 
     pub fn tamper(&mut self, block: Block) {
-        let block_index = block.index as usize;
+        let block_index = block.index() as usize;
         if block_index < self.len() {
             self.blocks[block_index] = block;
+            self.index = ChainIndex::from_blocks(&self.blocks);
         }
     }
 
-    // Checks if the genesis block is the same as the canonical one
-    fn validate_genesis_block(&self) -> Result<(), BlockchainError> {
-        if self.blocks[0] != Block::genesis() {
-            Err(BlockchainError::block(
-                0,
-                BlockErrorKind::GenesisBlockMismatch,
-            ))
+    // Finds the position of a block in the best chain
+    fn position(&self, hash: &str) -> Option<usize> {
+        self.blocks.iter().rposition(|block| block.hash == hash)
+    }
+
+    // Walks back from the given block through the block tree until it reaches the best chain.
+    // Returns the number of best chain blocks the branch shares and the branch blocks in order
+    fn branch_to(&self, hash: &str) -> Option<(usize, Vec<Block>)> {
+        let mut branch = vec![];
+        let mut current = hash.to_owned();
+        loop {
+            if let Some(position) = self.position(&current) {
+                branch.reverse();
+                return Some((position + 1, branch));
+            }
+
+            let block = self.tree.get(&current)?;
+            current = block.header.previous_hash.clone()?;
+            branch.push(block.clone());
+        }
+    }
+
+    // Takes a branch that forks off after the first `shared` blocks of the best chain. If the
+    // branch results in a better chain it's fully validated and becomes the best chain, otherwise
+    // its last block is checked against its parent and stored for later
+    fn connect_branch(
+        &mut self,
+        shared: usize,
+        branch: Vec<Block>,
+    ) -> Result<BlockStatus, BlockchainError> {
+        let mut candidate = Blockchain {
+            blocks: self.blocks[..shared].to_vec(),
+            mempool: Mempool::default(),
+            consensus: self.consensus.clone(),
+            spec: self.spec.clone(),
+            verifier: self.verifier.clone(),
+            tree: BlockTree::new(),
+            index: ChainIndex::new(),
+        };
+        candidate.blocks.extend(branch);
+        candidate.index = ChainIndex::from_blocks(&candidate.blocks);
+
+        if candidate.is_better_than(self) {
+            candidate.validate()?;
+            let branch = candidate.blocks.split_off(shared);
+            self.reorganize(shared, branch);
+            Ok(BlockStatus::Reorganized)
         } else {
-            Ok(())
+            let block = candidate.blocks.pop().expect("Branches are never empty");
+            let index = ChainIndex::from_blocks(&candidate.blocks);
+            block.validate(&candidate.blocks, &index, &self.consensus, &self.spec)?;
+            self.tree.insert(block);
+            Ok(BlockStatus::ForkStored)
         }
     }
 
-    // Validates the whole blockchain with the exception of the genesis block
-    fn validate_blocks(&self) -> Result<(), BlockchainError> {
-        for i in 1..self.blocks.len() {
-            self.blocks[i].validate(&self.blocks[i - 1])?;
+    // Switches the best chain over to an already validated branch which shares the first `shared`
+    // blocks with it. The blocks we roll back are kept in the block tree in case their branch
+    // overtakes us again, and their transactions are returned to the pending list
+    fn reorganize(&mut self, shared: usize, branch: Vec<Block>) {
+        let disconnected = self.blocks.split_off(shared);
+        for block in disconnected.iter().rev() {
+            self.index.rollback(block);
         }
-        Ok(())
+        for block in &branch {
+            self.tree.remove(&block.hash);
+            self.index.apply(block);
+        }
+        self.blocks.extend(branch);
+
+        // Rolled back transactions go first since they were created before the pending ones.
+        // Anything the new branch already contains or that can't be afforded anymore is dropped
+        // TODO(gustorn): This is horribly inefficient but it's the easiest way to keep
+        // the relevant part of the pending transaction list
+        let mut pending: Vec<Transaction> = disconnected
+            .iter()
+            .flat_map(|block| block.transactions.iter().cloned())
+            .collect();
+        pending.extend(self.mempool.take());
+        for block in disconnected {
+            self.tree.insert(block);
+        }
+
+        for tx in pending {
+            let _ = self.new_transaction(tx);
+        }
+        self.purge_expired();
+        self.prune_tree();
     }
 
-    // Checks if all user balances are positive or zero
-    fn validate_all_balances(&self) -> Result<(), BlockchainError> {
-        let mut balances = HashMap::new();
-        for tx in transactions!(self) {
-            if let Some(sender) = tx.sender() {
-                let balance = balances.entry(sender).or_insert(0);
-                *balance -= tx.amount();
-            }
-            let balance = balances.entry(tx.recipient()).or_insert(0);
-            *balance += tx.amount();
+    // Drops the side branch blocks that fork off too far below the new tip
+    fn prune_tree(&mut self) {
+        let tip = self.last_block().index();
+        self.tree.prune(tip.saturating_sub(MAX_FORK_DEPTH));
+    }
+
+    // Drops the pending transactions that expired now that the chain got longer, together with
+    // the transactions of the same senders that depend on them
+    fn purge_expired(&mut self) {
+        let height = self.last_block().index() + 1;
+        self.mempool.purge(height);
+    }
+
+    // Validates a block that builds directly on the tip of the best chain against the state of the
+    // chain. See `validate_block_state` for what that involves
+    fn validate_next_block(&self, block: &Block) -> Result<(), BlockchainError> {
+        block.validate(&self.blocks, &self.index, &self.consensus, &self.spec)?;
+        validate_block_state(block, &self.index)
+    }
+
+    // Checks if the genesis block is the one defined by our chain spec, allocations included. The
+    // genesis block isn't validated like the rest, so the allocations' amounts are checked here
+    fn validate_genesis_block(&self) -> Result<(), BlockchainError> {
+        let genesis = Block::genesis(&self.spec);
+        if self.blocks[0] != genesis {
+            return Err(BlockchainError::block(
+                0,
+                BlockErrorKind::GenesisBlockMismatch,
+            ));
         }
 
-        if balances.values().any(|&balance| balance < 0) {
-            Err(BlockchainError::chain(ChainErrorKind::InvalidBalance))
-        } else {
-            Ok(())
+        match genesis.transactions.iter().find(|tx| tx.amount() <= 0) {
+            Some(tx) => Err(BlockchainError::transaction(
+                *tx.id(),
+                TransactionErrorKind::InvalidAmount,
+            )),
+            None => Ok(()),
         }
     }
 
-    // Checks whether there are *any* duplicate transactions in the blockchain
-    fn validate_all_duplicate_transactions(&self) -> Result<(), BlockchainError> {
-        let mut transactions = HashSet::new();
-        for tx in transactions!(self) {
-            if transactions.contains(&tx.id()) {
-                return Err(BlockchainError::transaction(
-                    *tx.id(),
-                    TransactionErrorKind::DuplicateId,
-                ));
-            }
-            transactions.insert(tx.id());
+    // Rewards can only be added as part of a mined block
+    fn validate_user_transaction(&self, tx: &Transaction) -> Result<(), BlockchainError> {
+        match *tx {
+            Transaction::Reward(_) => Err(BlockchainError::transaction(
+                *tx.id(),
+                TransactionErrorKind::UnexpectedReward,
+            )),
+            _ => Ok(()),
         }
-        Ok(())
     }
 
-    // Checks whether there is *at least one* duplicate transaction in the blockchain (which
-    // matches the id of `transfer`).
-    fn validate_duplicate_transaction(&self, transfer: &Transfer) -> Result<(), BlockchainError> {
-        if transactions!(with_pending self).any(|tx| tx.id() == &transfer.id) {
+    // Checks whether the id of `tx` is already used in the blockchain, or by a pending transaction
+    // it doesn't replace
+    fn validate_duplicate_transaction(&self, tx: &Transaction) -> Result<(), BlockchainError> {
+        let pending = self.mempool.get(tx.id()).map_or(false, |x| !x.replaces(tx));
+        if self.index.contains(tx.id()) || pending {
             Err(BlockchainError::transaction(
-                transfer.id,
+                *tx.id(),
                 TransactionErrorKind::DuplicateId,
             ))
         } else {
@@ -164,39 +474,284 @@ impl Blockchain {
         }
     }
 
-    // Checks if the sender can actually afford the transaction
-    fn validate_sender_balance(&self, transfer: &Transfer) -> Result<(), BlockchainError> {
-        let balance: i64 = transactions!(with_pending self)
-            .map(|tx| tx.balance_change(&transfer.sender))
-            .sum();
+    // A transfer either continues the sender's nonces or replaces a pending transfer. Used up
+    // nonces are rejected, which is what stops transfers from being replayed
+    fn validate_nonce(&self, tx: &Transaction) -> Result<(), BlockchainError> {
+        let (sender, nonce) = match (tx.sender(), tx.nonce()) {
+            (Some(sender), Some(nonce)) => (sender, nonce),
+            _ => return Ok(()),
+        };
 
-        if balance < transfer.amount {
+        let confirmed = self.index.next_nonce(sender);
+        if nonce < confirmed || nonce > confirmed + self.mempool.transfers_from(sender) {
             Err(BlockchainError::transaction(
-                transfer.id,
-                TransactionErrorKind::InsufficientBalance,
+                *tx.id(),
+                TransactionErrorKind::InvalidNonce,
             ))
         } else {
             Ok(())
         }
     }
 
-    // A single block only accepts a limited number of transactions before it needs to be mined
-    fn validate_pending_transactions(&self) -> Result<(), BlockchainError> {
-        if self.transactions.len() >= PENDING_TRANSACTION_LIMIT {
-            Err(BlockchainError::chain(
-                ChainErrorKind::PendingTransactionLimitReached,
+    // Checks if the sender can actually afford the transaction. A pending transaction it would
+    // replace doesn't count
+    fn validate_sender_balance(&self, tx: &Transaction) -> Result<(), BlockchainError> {
+        let sender = match tx.sender() {
+            Some(sender) => sender,
+            None => return Ok(()),
+        };
+        let balance = self.index.balance(sender)
+            + self.mempool
+                .transactions()
+                .iter()
+                .filter(|x| !x.replaces(tx))
+                .chain(Some(tx))
+                .map(|x| x.balance_change(sender))
+                .sum::<i64>();
+
+        if balance < 0 {
+            Err(BlockchainError::transaction(
+                *tx.id(),
+                TransactionErrorKind::InsufficientBalance,
             ))
         } else {
             Ok(())
         }
     }
+
+    // Checks that the transaction doesn't unlock stake that isn't locked anymore once the pending
+    // transactions are applied
+    fn validate_pending_stakes(&self, tx: &Transaction) -> Result<(), BlockchainError> {
+        let index = self.last_block().index() + 1;
+        let mut stakes = self.index.stakes().clone();
+        for pending in self.mempool.transactions().iter().filter(|x| !x.replaces(tx)) {
+            stakes.apply(pending, index)?;
+        }
+        stakes.apply(tx, index)
+    }
+
+    // Checks whether a block on top of the best chain that already holds the `selected`
+    // transactions can hold this one too: a transfer has to continue its sender's nonces, its
+    // sender has to afford it, it can't unlock stake that isn't locked and its fee can't push the
+    // miner's reward past what an i64 can hold
+    fn fits_in_block(&self, selected: &[Transaction], tx: &Transaction) -> bool {
+        let index = self.last_block().index() + 1;
+        if tx.validate_height(index).is_err() {
+            return false;
+        }
+
+        if self.spec.miner_reward(index, selected.iter().chain(Some(tx))).is_none() {
+            return false;
+        }
+
+        if let (Some(sender), Some(nonce)) = (tx.sender(), tx.nonce()) {
+            let selected_transfers = selected
+                .iter()
+                .filter(|x| x.nonce().is_some() && x.sender() == Some(sender))
+                .count() as u64;
+            if nonce != self.index.next_nonce(sender) + selected_transfers {
+                return false;
+            }
+        }
+
+        let balance = tx.sender().map_or(0, |sender| {
+            let change: i64 = selected
+                .iter()
+                .chain(Some(tx))
+                .map(|x| x.balance_change(sender))
+                .sum();
+            self.index.balance(sender) + change
+        });
+        let mut stakes = self.index.stakes().clone();
+        balance >= 0 && selected.iter().chain(Some(tx)).all(|x| stakes.apply(x, index).is_ok())
+    }
 }
 
-impl Default for Blockchain {
+impl Blockchain<ProofOfWork> {
+    pub fn new() -> Self {
+        Blockchain::default()
+    }
+
+    pub fn with_params(params: ChainParams) -> Self {
+        Blockchain::from_spec(ChainSpec {
+            params,
+            ..ChainSpec::default()
+        })
+    }
+
+    // A proof of work chain that uses the proof of work parameters of the spec
+    pub fn from_spec(spec: ChainSpec) -> Self {
+        let consensus = ProofOfWork::with_params(spec.params.clone());
+        Blockchain::with_spec(spec, consensus)
+    }
+
+    // The best chain without the transaction data, for nodes that only follow the headers. This
+    // fails if the chain has been tampered with
+    pub fn headers(&self) -> Result<HeaderChain, BlockchainError> {
+        HeaderChain::from_headers(
+            &self.spec,
+            self.consensus.clone(),
+            self.blocks.iter().map(|block| block.header.clone()).collect(),
+        )
+    }
+}
+
+impl Blockchain<Engine> {
+    // The best chain without the transaction data. Only proof of work headers can be checked
+    // without the blocks, so this fails under the other engines
+    pub fn headers(&self) -> Result<HeaderChain, BlockchainError> {
+        match self.consensus {
+            Engine::Work(ref consensus) => HeaderChain::from_headers(
+                &self.spec,
+                consensus.clone(),
+                self.blocks.iter().map(|block| block.header.clone()).collect(),
+            ),
+            _ => Err(BlockchainError::chain(ChainErrorKind::NotProofOfWork)),
+        }
+    }
+}
+
+impl<C: Consensus> Default for Blockchain<C> {
     fn default() -> Self {
-        Self {
-            blocks: vec![Block::genesis()],
-            transactions: vec![],
+        Blockchain::with_consensus(C::default())
+    }
+}
+
+// Checks a block against the state of the chain before it: none of its transactions may already be
+// in the chain, every sender's transfers have to continue its nonces, none of its senders may end
+// up with a negative balance and no validator may unlock stake it doesn't have
+fn validate_block_state(block: &Block, index: &ChainIndex) -> Result<(), BlockchainError> {
+    let mut stakes = index.stakes().clone();
+    let mut ids = HashSet::new();
+    let mut nonces = HashMap::new();
+    for tx in &block.transactions {
+        if !ids.insert(tx.id()) || index.contains(tx.id()) {
+            return Err(BlockchainError::transaction(
+                *tx.id(),
+                TransactionErrorKind::DuplicateId,
+            ));
+        }
+
+        if let (Some(sender), Some(nonce)) = (tx.sender(), tx.nonce()) {
+            let next = nonces
+                .entry(sender)
+                .or_insert_with(|| index.next_nonce(sender));
+            if nonce != *next {
+                return Err(BlockchainError::transaction(
+                    *tx.id(),
+                    TransactionErrorKind::InvalidNonce,
+                ));
+            }
+            *next += 1;
+        }
+
+        stakes.apply(tx, block.index())?;
+    }
+
+    let senders: HashSet<&str> = block
+        .transactions
+        .iter()
+        .filter_map(|tx| tx.sender())
+        .collect();
+    for sender in senders {
+        // A block full of large transfers could overflow the sum, which can't be a valid balance
+        let balance = block
+            .transactions
+            .iter()
+            .fold(Some(index.balance(sender)), |balance, tx| {
+                balance.and_then(|balance| balance.checked_add(tx.balance_change(sender)))
+            });
+        if balance.map_or(true, |balance| balance < 0) {
+            return Err(BlockchainError::block(
+                block.index(),
+                BlockErrorKind::InvalidBalance,
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::ToHex;
+    use ring::signature;
+    use untrusted;
+
+    use core::{Allocation, ChainParams, ChainSpec, StakeAction, Transaction};
+    use core::index::ChainIndex;
+    use super::Blockchain;
+
+    fn key_pair(seed: u8) -> signature::Ed25519KeyPair {
+        signature::Ed25519KeyPair::from_seed_unchecked(untrusted::Input::from(&[seed; 32]))
+            .expect("The seed has the right length")
+    }
+
+    // A cheap proof of work chain where `address` starts out with 1000 coins
+    fn blockchain(address: &str) -> Blockchain {
+        Blockchain::from_spec(ChainSpec {
+            params: ChainParams::with_difficulty(2),
+            allocations: vec![
+                Allocation {
+                    recipient: String::from(address),
+                    amount: 1000,
+                },
+            ],
+            ..ChainSpec::default()
+        })
+    }
+
+    #[test]
+    fn reorganization_rolls_back_and_reapplies_the_index() {
+        let key_pair = key_pair(1);
+        let address: String = key_pair.public_key_bytes().to_hex();
+        let mut a = blockchain(&address);
+        let mut b = a.clone();
+
+        // Branch a locks 50 and pays 100 in one block
+        let lock = Transaction::stake(&address, StakeAction::Lock, 50, &key_pair);
+        a.new_transaction(lock).unwrap();
+        a.new_transaction(Transaction::transfer(&address, "x", 100, 5, 0, &key_pair))
+            .unwrap();
+        a.mine("a").unwrap();
+        assert_eq!(a.index.stakes().stake(&address), 50);
+        assert_eq!(a.balance(&address), 1000 - 50 - 105);
+        let mut original = a.clone();
+
+        // Branch b locks 30, unlocks 10 of it again and pays 1, which takes two blocks
+        let lock = Transaction::stake(&address, StakeAction::Lock, 30, &key_pair);
+        b.new_transaction(lock).unwrap();
+        b.new_transaction(Transaction::transfer(&address, "y", 1, 0, 0, &key_pair))
+            .unwrap();
+        b.mine("b").unwrap();
+        let unlock = Transaction::stake(&address, StakeAction::Unlock, 10, &key_pair);
+        b.new_transaction(unlock).unwrap();
+        b.mine("b").unwrap();
+
+        // Equal branches are decided by their tip hash, so a might already switch after the
+        // first block. Either way the index has to match the blocks it ends up with
+        for block in b.blocks[1..].iter().cloned() {
+            a.add_block(block).unwrap();
+            assert_eq!(a.index, ChainIndex::from_blocks(&a.blocks));
+        }
+        assert_eq!(a.blocks, b.blocks);
+        assert_eq!(a.index, ChainIndex::from_blocks(&a.blocks));
+        assert_eq!(a.index.stakes().stake(&address), 20);
+        assert_eq!(a.balance(&address), 1000 - 30 - 1);
+        assert_eq!(a.balance("a"), 0);
+        assert_eq!(a.next_nonce(&address), 1);
+
+        // Once branch a has more work again, the node switches back to it
+        original.mine("a").unwrap();
+        original.mine("a").unwrap();
+        for block in original.blocks[2..].iter().cloned() {
+            a.add_block(block).unwrap();
+            assert_eq!(a.index, ChainIndex::from_blocks(&a.blocks));
         }
+        assert_eq!(a.blocks, original.blocks);
+        assert_eq!(a.index, ChainIndex::from_blocks(&a.blocks));
+        assert_eq!(a.index.stakes().stake(&address), 50);
+        assert_eq!(a.balance(&address), 1000 - 50 - 105);
+        assert_eq!(a.balance("b"), 0);
+        a.validate().unwrap();
     }
 }
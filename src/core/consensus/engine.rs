@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use core::BlockchainError;
+use core::block::Block;
+use core::header::BlockHeader;
+use core::index::ChainIndex;
+use core::miner::MiningJob;
+
+use super::{Consensus, ProofOfAuthority, ProofOfStake, ProofOfWork};
+
+// Any of the consensus engines, for nodes that pick theirs when they start instead of when they
+// are compiled. Every call goes to the engine it holds
+#[derive(Debug, Clone)]
+pub enum Engine {
+    Work(ProofOfWork),
+    Authority(ProofOfAuthority),
+    Stake(ProofOfStake),
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::Work(ProofOfWork::default())
+    }
+}
+
+impl Consensus for Engine {
+    fn prepare(
+        &self,
+        chain: &[Block],
+        index: &ChainIndex,
+        header: &mut BlockHeader,
+    ) -> Result<(), BlockchainError> {
+        match *self {
+            Engine::Work(ref engine) => engine.prepare(chain, index, header),
+            Engine::Authority(ref engine) => engine.prepare(chain, index, header),
+            Engine::Stake(ref engine) => engine.prepare(chain, index, header),
+        }
+    }
+
+    fn seal(&self, block: Block, job: &Arc<MiningJob>) -> Result<Block, BlockchainError> {
+        match *self {
+            Engine::Work(ref engine) => engine.seal(block, job),
+            Engine::Authority(ref engine) => engine.seal(block, job),
+            Engine::Stake(ref engine) => engine.seal(block, job),
+        }
+    }
+
+    fn verify_seal(
+        &self,
+        chain: &[Block],
+        index: &ChainIndex,
+        header: &BlockHeader,
+    ) -> Result<String, BlockchainError> {
+        match *self {
+            Engine::Work(ref engine) => engine.verify_seal(chain, index, header),
+            Engine::Authority(ref engine) => engine.verify_seal(chain, index, header),
+            Engine::Stake(ref engine) => engine.verify_seal(chain, index, header),
+        }
+    }
+
+    fn verify_orphan(
+        &self,
+        chain: &[Block],
+        index: &ChainIndex,
+        header: &BlockHeader,
+    ) -> Result<String, BlockchainError> {
+        match *self {
+            Engine::Work(ref engine) => engine.verify_orphan(chain, index, header),
+            Engine::Authority(ref engine) => engine.verify_orphan(chain, index, header),
+            Engine::Stake(ref engine) => engine.verify_orphan(chain, index, header),
+        }
+    }
+
+    fn weight(&self, header: &BlockHeader) -> u64 {
+        match *self {
+            Engine::Work(ref engine) => engine.weight(header),
+            Engine::Authority(ref engine) => engine.weight(header),
+            Engine::Stake(ref engine) => engine.weight(header),
+        }
+    }
+}
+
+impl From<ProofOfWork> for Engine {
+    fn from(engine: ProofOfWork) -> Self {
+        Engine::Work(engine)
+    }
+}
+
+impl From<ProofOfAuthority> for Engine {
+    fn from(engine: ProofOfAuthority) -> Self {
+        Engine::Authority(engine)
+    }
+}
+
+impl From<ProofOfStake> for Engine {
+    fn from(engine: ProofOfStake) -> Self {
+        Engine::Stake(engine)
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use super::BlockchainError;
+use super::block::Block;
+use super::header::BlockHeader;
+use super::index::ChainIndex;
+use super::miner::MiningJob;
+
+pub mod engine;
+pub mod poa;
+pub mod pos;
+pub mod pow;
+
+pub use self::engine::Engine;
+pub use self::poa::ProofOfAuthority;
+pub use self::pos::ProofOfStake;
+pub use self::pow::ProofOfWork;
+
+// The rules that decide who gets to create the next block and which chain is the best one. The
+// rest of the chain logic (linking blocks together, transactions, balances) is the same no matter
+// which consensus engine is used. Engines that depend on the state of the chain (like the locked
+// stake) get the `index` of the state `chain` leaves behind, so they never have to replay it
+pub trait Consensus: Debug + Clone + Default {
+    // Fills in the consensus specific parts of a block template that builds on top of `chain`.
+    // Fails if we aren't allowed to create that block
+    fn prepare(
+        &self,
+        chain: &[Block],
+        index: &ChainIndex,
+        header: &mut BlockHeader,
+    ) -> Result<(), BlockchainError>;
+
+    // Seals a prepared block so that other nodes accept it and fills in its hash. Fails if the job
+    // was cancelled before the block could be sealed or if we aren't allowed to seal it
+    fn seal(&self, block: Block, job: &Arc<MiningJob>) -> Result<Block, BlockchainError>;
+
+    // Checks that the header carries a valid seal for a block following `chain` and returns its
+    // hash. The header is already known to link to the last block of `chain`
+    fn verify_seal(
+        &self,
+        chain: &[Block],
+        index: &ChainIndex,
+        header: &BlockHeader,
+    ) -> Result<String, BlockchainError>;
+
+    // Checks what can be checked of a header whose parent isn't known, given the best chain
+    // `chain`, and returns its hash. Orphans can't be fully validated, but this keeps anyone from
+    // filling the orphan pool with blocks that took neither work nor a key to make
+    fn verify_orphan(
+        &self,
+        chain: &[Block],
+        index: &ChainIndex,
+        header: &BlockHeader,
+    ) -> Result<String, BlockchainError>;
+
+    // How much a block counts towards its chain when choosing between competing chains
+    fn weight(&self, header: &BlockHeader) -> u64;
+}
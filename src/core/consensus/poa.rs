@@ -0,0 +1,118 @@
+use std::fmt;
+use std::sync::Arc;
+
+use hex::ToHex;
+use ring::signature::Ed25519KeyPair;
+
+use core::{BlockErrorKind, BlockchainError, ChainErrorKind};
+use core::block::Block;
+use core::header::BlockHeader;
+use core::index::ChainIndex;
+use core::miner::MiningJob;
+
+use super::Consensus;
+
+// Proof of authority: only a fixed set of authorities may create blocks, taking turns in the order
+// they are listed. An authority seals a block by signing its header, so sealing is instant
+#[derive(Clone, Default)]
+pub struct ProofOfAuthority {
+    // The hex encoded Ed25519 public keys of the authorities
+    pub authorities: Vec<String>,
+
+    // Our own key if we are one of the authorities, nodes without one can only verify blocks
+    signer: Option<Arc<Ed25519KeyPair>>,
+}
+
+impl ProofOfAuthority {
+    pub fn new(authorities: Vec<String>) -> Self {
+        Self {
+            authorities,
+            signer: None,
+        }
+    }
+
+    pub fn with_signer(authorities: Vec<String>, key_pair: Ed25519KeyPair) -> Self {
+        Self {
+            authorities,
+            signer: Some(Arc::new(key_pair)),
+        }
+    }
+
+    // The authority whose turn it is to seal the block at the given height
+    pub fn authority(&self, index: u64) -> Option<&str> {
+        if self.authorities.is_empty() {
+            None
+        } else {
+            let turn = index % self.authorities.len() as u64;
+            Some(&self.authorities[turn as usize])
+        }
+    }
+}
+
+impl Consensus for ProofOfAuthority {
+    fn prepare(
+        &self,
+        _chain: &[Block],
+        _index: &ChainIndex,
+        header: &mut BlockHeader,
+    ) -> Result<(), BlockchainError> {
+        header.difficulty = 0;
+        Ok(())
+    }
+
+    fn seal(&self, mut block: Block, _job: &Arc<MiningJob>) -> Result<Block, BlockchainError> {
+        let signer = match self.signer {
+            Some(ref signer) => signer,
+            None => return Err(BlockchainError::chain(ChainErrorKind::NotAuthority)),
+        };
+
+        let public_key = signer.public_key_bytes().to_hex();
+        if self.authority(block.index()) != Some(public_key.as_ref()) {
+            return Err(BlockchainError::chain(ChainErrorKind::NotAuthority));
+        }
+
+        block.header.sign(signer);
+        block.hash = block.header.hash();
+        Ok(block)
+    }
+
+    fn verify_seal(
+        &self,
+        _chain: &[Block],
+        _index: &ChainIndex,
+        header: &BlockHeader,
+    ) -> Result<String, BlockchainError> {
+        match self.authority(header.index) {
+            Some(authority) if header.verify_signature(authority) => Ok(header.hash()),
+            _ => Err(BlockchainError::block(
+                header.index,
+                BlockErrorKind::InvalidSignature,
+            )),
+        }
+    }
+
+    // The authority only depends on the height, so orphans can be checked just like any block
+    fn verify_orphan(
+        &self,
+        chain: &[Block],
+        index: &ChainIndex,
+        header: &BlockHeader,
+    ) -> Result<String, BlockchainError> {
+        self.verify_seal(chain, index, header)
+    }
+
+    // Every block takes the same effort to create, so the longest chain wins
+    fn weight(&self, _header: &BlockHeader) -> u64 {
+        1
+    }
+}
+
+// Key pairs can't be printed, so only show whether we have one
+impl fmt::Debug for ProofOfAuthority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProofOfAuthority")
+            .field("authorities", &self.authorities)
+            .field("signer", &self.signer.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,142 @@
+use std::fmt;
+use std::sync::Arc;
+
+use hex::ToHex;
+use ring::signature::Ed25519KeyPair;
+
+use core::{BlockErrorKind, BlockchainError, ChainErrorKind};
+use core::block::Block;
+use core::header::BlockHeader;
+use core::index::ChainIndex;
+use core::miner::MiningJob;
+
+use super::Consensus;
+
+// Proof of stake: the validator that creates the next block is picked at random, weighted by the
+// active stake of every validator (see `StakeLedger::select`). The genesis hash and the index of
+// the new block seed the choice, and the chosen validator seals the block by signing its header
+#[derive(Clone, Default)]
+pub struct ProofOfStake {
+    // The hex encoded public key of the validator that creates blocks while nobody has any stake
+    // locked, otherwise there would be nobody to create the first blocks
+    pub bootstrap: String,
+
+    // Our own key if we are a validator, nodes without one can only verify blocks
+    signer: Option<Arc<Ed25519KeyPair>>,
+}
+
+impl ProofOfStake {
+    pub fn new<B: AsRef<str>>(bootstrap: B) -> Self {
+        Self {
+            bootstrap: String::from(bootstrap.as_ref()),
+            signer: None,
+        }
+    }
+
+    pub fn with_signer<B: AsRef<str>>(bootstrap: B, key_pair: Ed25519KeyPair) -> Self {
+        Self {
+            bootstrap: String::from(bootstrap.as_ref()),
+            signer: Some(Arc::new(key_pair)),
+        }
+    }
+
+    // The validator chosen to create the block following `chain`, whose state is in `index`. The
+    // seed can't depend on the contents of earlier blocks, such as the previous block's hash, or
+    // their creators could try different transactions and timestamps until they are chosen
+    // again. Since the seed is known in advance, new stake only counts once it's active
+    pub fn validator(&self, chain: &[Block], index: &ChainIndex) -> String {
+        let genesis = chain.first().expect("Zero-length chains are invalid");
+        let height = chain.len() as u64;
+        let seed = format!("{}{}", genesis.hash, height);
+        index
+            .stakes()
+            .select(&seed, height)
+            .unwrap_or(&self.bootstrap)
+            .to_owned()
+    }
+}
+
+impl Consensus for ProofOfStake {
+    // Only the chosen validator can create the block, so there is no point in preparing it
+    // anywhere else
+    fn prepare(
+        &self,
+        chain: &[Block],
+        index: &ChainIndex,
+        header: &mut BlockHeader,
+    ) -> Result<(), BlockchainError> {
+        let signer = match self.signer {
+            Some(ref signer) => signer,
+            None => return Err(BlockchainError::chain(ChainErrorKind::NotValidator)),
+        };
+
+        if self.validator(chain, index) != signer.public_key_bytes().to_hex() {
+            return Err(BlockchainError::chain(ChainErrorKind::NotValidator));
+        }
+        header.difficulty = 0;
+        Ok(())
+    }
+
+    fn seal(&self, mut block: Block, _job: &Arc<MiningJob>) -> Result<Block, BlockchainError> {
+        match self.signer {
+            Some(ref signer) => {
+                block.header.sign(signer);
+                block.hash = block.header.hash();
+                Ok(block)
+            }
+            None => Err(BlockchainError::chain(ChainErrorKind::NotValidator)),
+        }
+    }
+
+    fn verify_seal(
+        &self,
+        chain: &[Block],
+        index: &ChainIndex,
+        header: &BlockHeader,
+    ) -> Result<String, BlockchainError> {
+        if header.verify_signature(&self.validator(chain, index)) {
+            Ok(header.hash())
+        } else {
+            Err(BlockchainError::block(
+                header.index,
+                BlockErrorKind::InvalidSignature,
+            ))
+        }
+    }
+
+    // Which validator an orphan needs depends on blocks we haven't seen, but it has to be signed
+    // by the bootstrap validator or by somebody who has stake locked
+    fn verify_orphan(
+        &self,
+        _chain: &[Block],
+        index: &ChainIndex,
+        header: &BlockHeader,
+    ) -> Result<String, BlockchainError> {
+        let mut validators = Some(self.bootstrap.as_ref())
+            .into_iter()
+            .chain(index.stakes().validators());
+        if validators.any(|validator| header.verify_signature(validator)) {
+            Ok(header.hash())
+        } else {
+            Err(BlockchainError::block(
+                header.index,
+                BlockErrorKind::InvalidSignature,
+            ))
+        }
+    }
+
+    // Creating a block takes no work, so the longest chain wins
+    fn weight(&self, _header: &BlockHeader) -> u64 {
+        1
+    }
+}
+
+// Key pairs can't be printed, so only show whether we have one
+impl fmt::Debug for ProofOfStake {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProofOfStake")
+            .field("bootstrap", &self.bootstrap)
+            .field("signer", &self.signer.is_some())
+            .finish()
+    }
+}
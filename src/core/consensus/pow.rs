@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use core::{BlockErrorKind, BlockchainError, ChainErrorKind};
+use core::block::Block;
+use core::header::BlockHeader;
+use core::index::ChainIndex;
+use core::miner::{Miner, MiningJob};
+use core::params::ChainParams;
+
+use super::Consensus;
+
+// Proof of work: the next block can be created by anyone who finds a proof that gives its header
+// a hash with enough leading zero bits, and the chain with the most expected work wins
+#[derive(Debug, Clone, Default)]
+pub struct ProofOfWork {
+    pub params: ChainParams,
+    pub miner: Miner,
+}
+
+impl ProofOfWork {
+    pub fn new(params: ChainParams, miner: Miner) -> Self {
+        Self { params, miner }
+    }
+
+    pub fn with_params(params: ChainParams) -> Self {
+        Self {
+            params,
+            ..ProofOfWork::default()
+        }
+    }
+
+    // Checks the header's difficulty against the retarget schedule and its proof against the
+    // difficulty using the chain's proof of work algorithm. Only needs the previous headers, which
+    // is what allows following a chain of headers without the block bodies
+    pub fn verify_header<H: AsRef<BlockHeader>>(
+        &self,
+        chain: &[H],
+        header: &BlockHeader,
+    ) -> Result<String, BlockchainError> {
+        if header.difficulty != self.params.next_difficulty(chain) {
+            Err(BlockchainError::block(
+                header.index,
+                BlockErrorKind::DifficultyMismatch,
+            ))
+        } else {
+            header.try_hash(self.params.algorithm)
+        }
+    }
+}
+
+impl Consensus for ProofOfWork {
+    fn prepare(
+        &self,
+        chain: &[Block],
+        _index: &ChainIndex,
+        header: &mut BlockHeader,
+    ) -> Result<(), BlockchainError> {
+        header.difficulty = self.params.next_difficulty(chain);
+        Ok(())
+    }
+
+    fn seal(&self, block: Block, job: &Arc<MiningJob>) -> Result<Block, BlockchainError> {
+        self.miner
+            .mine(block, self.params.algorithm, job)
+            .ok_or_else(|| BlockchainError::chain(ChainErrorKind::MiningCancelled))
+    }
+
+    fn verify_seal(
+        &self,
+        chain: &[Block],
+        _index: &ChainIndex,
+        header: &BlockHeader,
+    ) -> Result<String, BlockchainError> {
+        self.verify_header(chain, header)
+    }
+
+    // The difficulty moves by at most one bit per retarget, so an orphan near our tip can't claim
+    // much less than the difficulty of the block after the tip. Its proof is then checked against
+    // what it claims
+    fn verify_orphan(
+        &self,
+        chain: &[Block],
+        _index: &ChainIndex,
+        header: &BlockHeader,
+    ) -> Result<String, BlockchainError> {
+        if header.difficulty + 1 < self.params.next_difficulty(chain) {
+            Err(BlockchainError::block(
+                header.index,
+                BlockErrorKind::DifficultyMismatch,
+            ))
+        } else {
+            header.try_hash(self.params.algorithm)
+        }
+    }
+
+    fn weight(&self, header: &BlockHeader) -> u64 {
+        header.work()
+    }
+}
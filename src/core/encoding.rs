@@ -0,0 +1,128 @@
+use bincode;
+use serde::Serialize;
+
+// Bumped whenever the layout of any of the hashed or signed structures changes, so encodings
+// produced under different rules can never be mistaken for each other
+pub const ENCODING_VERSION: u8 = 5;
+
+// Encodes a value into the canonical byte representation used for hashing and signing. The
+// output is the version byte followed by the bincode encoding of the value: fields in declaration
+// order, little-endian fixed width integers, strings and sequences prefixed by their length as a
+// u64 and options prefixed by a 0 or 1 byte. ENCODING.md describes the format in full
+pub fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut bytes = vec![ENCODING_VERSION];
+    bincode::serialize_into(&mut bytes, value, bincode::Infinite)
+        .expect("Blockchain primitives must be able to be serialized into a byte vector");
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::{FromHex, ToHex};
+    use ring::{digest, signature};
+    use untrusted;
+    use uuid::Uuid;
+
+    use core::{BlockHeader, ChainSpec, Transaction, Transfer};
+    use super::{encode, ENCODING_VERSION};
+
+    // The test vectors from ENCODING.md, which other implementations check their encoding against
+    const PUBLIC_KEY: &str = "03a107bff3ce10be1d70dd18e74bc09967e4d6309ba50d5f1ddc8664125531b8";
+    const TRANSFER_BYTES: &str = concat!(
+        "05000102030405060708090a0b0c0d0e0f4000000000000000303361313037626666336365313062",
+        "65316437306464313865373462633039393637653464363330396261353064356631646463383636",
+        "34313235353331623840000000000000006666666666666666666666666666666666666666666666",
+        "66666666666666666666666666666666666666666666666666666666666666666666666666666666",
+        "663200000000000000020000000000000003000000000000000001e803000000000000",
+    );
+    const TRANSFER_SIGNATURE: &str = concat!(
+        "40b6536ea8c4057ccbe51b43820f031ad37721578a0f5a90bbdf2d2967c9df92",
+        "11157bcc70b13b52ded12fb60493de4054be580d59869bc408feb134d11d1308",
+    );
+    const TRANSFER_LEAF_HASH: &str = concat!(
+        "de4deca1036d9146759ff4238b58f3ca97ea16fb077a41939862ceb777e7e05c",
+        "e15166daa7528e9f23521f01099be1d8f6e994ca23edc0fca0f8188bc2d23005",
+    );
+    const HEADER_BYTES: &str = concat!(
+        "050100000000000000002f6859000000001000000039300000000000000700000000000000010000",
+        "00000000000080000000000000006162616261626162616261626162616261626162616261626162",
+        "61626162616261626162616261626162616261626162616261626162616261626162616261626162",
+        "61626162616261626162616261626162616261626162616261626162616261626162616261626162",
+        "61626162616261626162616261626162616261626162",
+    );
+    const HEADER_HASH: &str = concat!(
+        "8d1ef17a967ab661966ee625991284211cd7c47258249ca71ac04e0e0e0e7e5a",
+        "d7c8896accd119f2bec0a667ac687ce1a5ba4d96299a4128c63e19f874e26a82",
+    );
+    const SPEC_BYTES: &str = concat!(
+        "0507000000000000006972696469756d00000000000000006400000000000000e803000000",
+        "0000000400000000000000100000000a000000000000001e00000000000000000000000000",
+        "000000000000",
+    );
+    const SPEC_HASH: &str = concat!(
+        "13c2d2a57c0a66e348b9d02541d3478c89d434c897ab83635a3395ad2c805608",
+        "a46b62008b4970d4658ac109c773be9391029cc25848c2b17cce87eb5bd837a6",
+    );
+
+    // The key pair of the test vectors is generated from the seed 0, 1, ..., 31
+    fn key_pair() -> signature::Ed25519KeyPair {
+        let seed: Vec<u8> = (0..32).collect();
+        signature::Ed25519KeyPair::from_seed_unchecked(untrusted::Input::from(&seed))
+            .expect("The seed has the right length")
+    }
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        Vec::<u8>::from_hex(hex).expect("Test vectors are valid hex")
+    }
+
+    #[test]
+    fn transfer_vector() {
+        let key_pair = key_pair();
+        assert_eq!(key_pair.public_key_bytes().to_hex(), PUBLIC_KEY);
+
+        // Ed25519 signatures are deterministic: the documented bytes have to produce the
+        // documented signature, and the transfer only verifies if it encodes to the same bytes
+        let message = bytes(TRANSFER_BYTES);
+        assert_eq!(message[0], ENCODING_VERSION);
+        assert_eq!(key_pair.sign(&message).as_ref().to_hex(), TRANSFER_SIGNATURE);
+
+        let transfer = Transfer {
+            id: Uuid::from_bytes(&(0..16).collect::<Vec<u8>>()).unwrap(),
+            amount: 50,
+            fee: 2,
+            nonce: 3,
+            valid_after: None,
+            valid_until: Some(1000),
+            sender: PUBLIC_KEY.to_owned(),
+            recipient: "ff".repeat(32),
+            signature: TRANSFER_SIGNATURE.to_owned(),
+        };
+        assert!(transfer.validate().is_ok());
+        assert_eq!(Transaction::from(transfer).hash(), TRANSFER_LEAF_HASH);
+    }
+
+    #[test]
+    fn header_vector() {
+        let header = BlockHeader {
+            index: 1,
+            timestamp: 1_500_000_000,
+            difficulty: 16,
+            proof: 12345,
+            extra_nonce: 7,
+            previous_hash: Some(String::new()),
+            merkle_root: "ab".repeat(64),
+            signature: None,
+        };
+        let encoded = bytes(HEADER_BYTES);
+        assert_eq!(encoded[0], ENCODING_VERSION);
+        assert_eq!(digest::digest(&digest::SHA512, &encoded).as_ref().to_hex(), HEADER_HASH);
+        assert_eq!(header.hash(), HEADER_HASH);
+    }
+
+    #[test]
+    fn spec_vector() {
+        let spec = ChainSpec::default();
+        assert_eq!(encode(&spec), bytes(SPEC_BYTES));
+        assert_eq!(spec.hash(), SPEC_HASH);
+    }
+}
@@ -5,8 +5,17 @@ pub enum TransactionErrorKind {
     InvalidSignature,
     InsufficientBalance,
     InvalidAmount,
+    InvalidFee,
     DuplicateId,
     MismatchedMinerReward,
+    InsufficientStake,
+    InvalidEvidence,
+    DuplicateEvidence,
+    UnexpectedReward,
+    InsufficientReplacementFee,
+    InvalidNonce,
+    NotYetValid,
+    Expired,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
@@ -14,14 +23,28 @@ pub enum BlockErrorKind {
     GenesisBlockMismatch,
     HashMismatch,
     InvalidProof,
+    DifficultyMismatch,
     PreviousHashMismatch,
+    IndexMismatch,
+    MerkleRootMismatch,
+    InvalidSignature,
     InvalidRewardCount,
+    TooManyTransactions,
+    InvalidBalance,
+    InvalidFees,
+    TimestampTooEarly,
+    TimestampTooLate,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Serialize)]
 pub enum ChainErrorKind {
-    InvalidBalance,
-    PendingTransactionLimitReached,
+    MempoolFull,
+    MiningCancelled,
+    NotAuthority,
+    NotValidator,
+    IndexMismatch,
+    NotProofOfWork,
+    StaleBlock,
 }
 
 #[derive(Debug, Clone, Serialize)]
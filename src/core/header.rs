@@ -0,0 +1,258 @@
+use std::cmp;
+
+use chrono;
+use hex::ToHex;
+use ring::signature::Ed25519KeyPair;
+
+use super::Verify;
+use super::{BlockErrorKind, BlockchainError};
+use super::block::Block;
+use super::consensus::ProofOfWork;
+use super::memory_hard;
+use super::params::PowAlgorithm;
+use super::spec::ChainSpec;
+
+// A header's timestamp has to be later than the median timestamp of this many headers before it,
+// so a single miner can't move the time backwards
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+// How far a header's timestamp can be ahead of our own clock
+pub const MAX_FUTURE_DRIFT_SECS: i64 = 2 * 60 * 60;
+
+// Everything about a block except for its transactions, which are committed to by the Merkle
+// root. The proof of work only covers the header, so a chain of headers can be validated without
+// ever downloading the block bodies
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub timestamp: i64,
+    pub difficulty: u32,
+    pub proof: u64,
+    pub extra_nonce: u64,
+    pub previous_hash: Option<String>,
+    pub merkle_root: String,
+
+    // Only used by proof of authority, where it's the authority's signature over the rest of the
+    // header. It can't be part of the hash since it signs the same bytes
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+// The part of the header that the block hash (and so the proof of work) is calculated from
+#[derive(Debug, Serialize)]
+pub struct VerifiedBlock<'a> {
+    pub index: u64,
+    pub timestamp: i64,
+    pub difficulty: u32,
+    pub proof: u64,
+    pub extra_nonce: u64,
+    pub previous_hash: Option<&'a str>,
+    pub merkle_root: &'a str,
+}
+
+// A chain of block headers without the transactions. It can verify that the headers are linked
+// together and that each of them has the required proof of work, which is enough to follow the
+// best chain without storing any of the block data. Only proof of work can be checked without the
+// transactions, so header chains are tied to it
+#[derive(Debug, Clone)]
+pub struct HeaderChain {
+    headers: Vec<BlockHeader>,
+    tip_hash: String,
+    consensus: ProofOfWork,
+}
+
+impl BlockHeader {
+    pub fn hash(&self) -> String {
+        self.calculate_hash::<VerifiedBlock>()
+    }
+
+    // The expected number of hashes it took to find this header's proof. Every bit of difficulty
+    // doubles it (saturating well above anything we could ever mine)
+    pub fn work(&self) -> u64 {
+        1 << cmp::min(self.difficulty, 63)
+    }
+
+    // Checks that the header directly follows the last one of `chain`, and that its timestamp is
+    // later than the median of the recent headers without being too far in the future. Whether it
+    // was allowed to be created is up to the consensus engine
+    pub fn validate<H: AsRef<BlockHeader>>(
+        &self,
+        chain: &[H],
+        previous_hash: &str,
+    ) -> Result<(), BlockchainError> {
+        let previous = chain.last().expect("Zero-length chains are invalid").as_ref();
+        let latest = chrono::Utc::now()
+            .timestamp()
+            .saturating_add(MAX_FUTURE_DRIFT_SECS);
+        if self.index != previous.index + 1 {
+            Err(self.error(BlockErrorKind::IndexMismatch))
+        } else if Some(previous_hash) != self.previous_hash.as_ref().map(|x| x.as_ref()) {
+            Err(self.error(BlockErrorKind::PreviousHashMismatch))
+        } else if self.timestamp <= median_time_past(chain) {
+            Err(self.error(BlockErrorKind::TimestampTooEarly))
+        } else if self.timestamp > latest {
+            Err(self.error(BlockErrorKind::TimestampTooLate))
+        } else {
+            Ok(())
+        }
+    }
+
+    // The hash the difficulty is checked against with the given proof of work algorithm
+    pub fn pow_hash(&self, algorithm: PowAlgorithm) -> String {
+        match algorithm {
+            PowAlgorithm::Sha512 => self.hash(),
+            PowAlgorithm::MemoryHard { blocks } => {
+                memory_hard::hash(&self.to_bytes::<VerifiedBlock>(), blocks)
+            }
+        }
+    }
+
+    // Calculates and returns the hash of this header if its proof is valid
+    pub fn try_hash(&self, algorithm: PowAlgorithm) -> Result<String, BlockchainError> {
+        let pow_hash = self.pow_hash(algorithm);
+        if leading_zero_bits(&pow_hash) < self.difficulty {
+            Err(self.error(BlockErrorKind::InvalidProof))
+        } else if algorithm == PowAlgorithm::Sha512 {
+            Ok(pow_hash)
+        } else {
+            Ok(self.hash())
+        }
+    }
+
+    // Signs the header with the given key. The signature covers the same bytes as the hash
+    pub fn sign(&mut self, key_pair: &Ed25519KeyPair) {
+        let message = (&*self).to_bytes::<VerifiedBlock>();
+        self.signature = Some(key_pair.sign(message.as_ref()).to_hex());
+    }
+
+    // Checks whether the header was signed by the owner of the given public key
+    pub fn verify_signature(&self, public_key: &str) -> bool {
+        self.signature.as_ref().map_or(false, |signature| {
+            let message = self.to_bytes::<VerifiedBlock>();
+            super::verify_signature(public_key, &message, signature)
+        })
+    }
+
+    fn error(&self, kind: BlockErrorKind) -> BlockchainError {
+        BlockchainError::block(self.index, kind)
+    }
+}
+
+impl HeaderChain {
+    pub fn new(spec: &ChainSpec, consensus: ProofOfWork) -> Self {
+        let genesis = Block::genesis(spec);
+        Self {
+            headers: vec![genesis.header],
+            tip_hash: genesis.hash,
+            consensus,
+        }
+    }
+
+    // Builds a header chain from a list of headers starting with the spec's genesis block,
+    // validating every one of them along the way
+    pub fn from_headers(
+        spec: &ChainSpec,
+        consensus: ProofOfWork,
+        headers: Vec<BlockHeader>,
+    ) -> Result<Self, BlockchainError> {
+        let mut headers = headers.into_iter();
+        match headers.next() {
+            Some(ref genesis) if *genesis == Block::genesis(spec).header => {}
+            _ => {
+                return Err(BlockchainError::block(
+                    0,
+                    BlockErrorKind::GenesisBlockMismatch,
+                ))
+            }
+        }
+
+        let mut chain = HeaderChain::new(spec, consensus);
+        for header in headers {
+            chain.push(header)?;
+        }
+        Ok(chain)
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    pub fn headers(&self) -> &[BlockHeader] {
+        &self.headers
+    }
+
+    pub fn last_header(&self) -> &BlockHeader {
+        self.headers.last().expect("Zero-length chains are invalid")
+    }
+
+    pub fn tip_hash(&self) -> &str {
+        &self.tip_hash
+    }
+
+    pub fn total_work(&self) -> u64 {
+        self.headers
+            .iter()
+            .fold(0, |total: u64, header| total.saturating_add(header.work()))
+    }
+
+    // Appends a header to the end of the chain if it links to the current tip and carries the
+    // proof of work the retarget schedule requires. Returns the hash of the new tip
+    pub fn push(&mut self, header: BlockHeader) -> Result<String, BlockchainError> {
+        header.validate(&self.headers, &self.tip_hash)?;
+        let hash = self.consensus.verify_header(&self.headers, &header)?;
+        self.headers.push(header);
+        self.tip_hash = hash.clone();
+        Ok(hash)
+    }
+}
+
+// The median timestamp of the last `MEDIAN_TIME_SPAN` headers of the chain
+pub fn median_time_past<H: AsRef<BlockHeader>>(chain: &[H]) -> i64 {
+    let start = chain.len().saturating_sub(MEDIAN_TIME_SPAN);
+    let mut timestamps: Vec<i64> = chain[start..]
+        .iter()
+        .map(|header| header.as_ref().timestamp)
+        .collect();
+    timestamps.sort();
+    timestamps[timestamps.len() / 2]
+}
+
+impl<'a> From<&'a BlockHeader> for VerifiedBlock<'a> {
+    fn from(header: &BlockHeader) -> VerifiedBlock {
+        VerifiedBlock {
+            index: header.index,
+            timestamp: header.timestamp,
+            difficulty: header.difficulty,
+            proof: header.proof,
+            extra_nonce: header.extra_nonce,
+            previous_hash: header.previous_hash.as_ref().map(|x| x.as_ref()),
+            merkle_root: &header.merkle_root,
+        }
+    }
+}
+
+impl AsRef<BlockHeader> for BlockHeader {
+    fn as_ref(&self) -> &BlockHeader {
+        self
+    }
+}
+
+// Counts the leading zero bits of a hex-encoded hash. The difficulty of a block is the minimum
+// number of these its hash needs to have
+fn leading_zero_bits(hash: &str) -> u32 {
+    let mut bits = 0;
+    for digit in hash.chars().filter_map(|c| c.to_digit(16)) {
+        if digit == 0 {
+            bits += 4;
+        } else {
+            // Digits are 4 bits wide, but leading_zeros counts from the top of a u32
+            bits += digit.leading_zeros() - 28;
+            break;
+        }
+    }
+    bits
+}
@@ -0,0 +1,123 @@
+use std::collections::{HashMap, HashSet};
+
+use uuid::Uuid;
+
+use super::block::Block;
+use super::stake::StakeLedger;
+use super::transaction::Transaction;
+
+// The account state of the best chain, kept up to date block by block so checking a transaction
+// doesn't need a scan over every transaction in the chain: the transaction ids already in use,
+// the next nonce of every sender, the balance of every account and the stake every validator has
+// locked. Accounts without a balance or nonce aren't stored, so an index that was rolled back
+// equals one built from scratch
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainIndex {
+    ids: HashSet<Uuid>,
+    nonces: HashMap<String, u64>,
+    balances: HashMap<String, i64>,
+    stakes: StakeLedger,
+}
+
+impl ChainIndex {
+    pub fn new() -> Self {
+        ChainIndex::default()
+    }
+
+    pub fn from_blocks(blocks: &[Block]) -> Self {
+        let mut index = ChainIndex::new();
+        for block in blocks {
+            index.apply(block);
+        }
+        index
+    }
+
+    // Adds the transactions of a block appended to the chain
+    pub fn apply(&mut self, block: &Block) {
+        for tx in &block.transactions {
+            self.ids.insert(*tx.id());
+            if let (Some(sender), Some(nonce)) = (tx.sender(), tx.nonce()) {
+                self.set_nonce(sender, nonce + 1);
+            }
+            for address in accounts(tx) {
+                self.add_balance(address, tx.balance_change(address));
+            }
+            // Only blocks that passed validation are applied (unless the chain was tampered with,
+            // in which case it fails validation anyway), so the stake changes are always valid
+            let _ = self.stakes.apply(tx, block.index());
+        }
+        self.pay_out(block.index(), 1);
+    }
+
+    // Undoes `apply` for the last block of the chain when it's rolled back
+    pub fn rollback(&mut self, block: &Block) {
+        self.pay_out(block.index(), -1);
+        for tx in block.transactions.iter().rev() {
+            self.ids.remove(tx.id());
+            if let (Some(sender), Some(nonce)) = (tx.sender(), tx.nonce()) {
+                self.set_nonce(sender, nonce);
+            }
+            for address in accounts(tx) {
+                self.add_balance(address, -tx.balance_change(address));
+            }
+            self.stakes.revert(tx, block.index());
+        }
+    }
+
+    pub fn contains(&self, id: &Uuid) -> bool {
+        self.ids.contains(id)
+    }
+
+    // The nonce the sender's next transfer has to use
+    pub fn next_nonce(&self, sender: &str) -> u64 {
+        self.nonces.get(sender).cloned().unwrap_or(0)
+    }
+
+    pub fn balance(&self, address: &str) -> i64 {
+        self.balances.get(address).cloned().unwrap_or(0)
+    }
+
+    pub fn stakes(&self) -> &StakeLedger {
+        &self.stakes
+    }
+
+    // Credits (or with a sign of -1 takes back) the unlocked stake paid out by the block
+    fn pay_out(&mut self, index: u64, sign: i64) {
+        let payouts: Vec<(String, i64)> = self.stakes
+            .payouts(index)
+            .into_iter()
+            .map(|(validator, amount)| (validator.to_owned(), amount))
+            .collect();
+        for (validator, amount) in payouts {
+            self.add_balance(&validator, sign * amount);
+        }
+    }
+
+    fn set_nonce(&mut self, sender: &str, nonce: u64) {
+        if nonce == 0 {
+            self.nonces.remove(sender);
+        } else {
+            self.nonces.insert(sender.to_owned(), nonce);
+        }
+    }
+
+    fn add_balance(&mut self, address: &str, change: i64) {
+        let balance = self.balance(address) + change;
+        if balance == 0 {
+            self.balances.remove(address);
+        } else {
+            self.balances.insert(address.to_owned(), balance);
+        }
+    }
+}
+
+// The accounts whose balance the transaction might change, each of them once
+fn accounts(tx: &Transaction) -> Vec<&str> {
+    let mut accounts: Vec<&str> = tx.sender().into_iter().collect();
+    if let Some(recipient) = tx.recipient() {
+        if !accounts.contains(&recipient) {
+            accounts.push(recipient);
+        }
+    }
+    accounts
+}
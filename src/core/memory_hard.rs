@@ -0,0 +1,49 @@
+use std::cmp;
+
+use hex::ToHex;
+use ring::digest;
+
+// The size of a SHA-512 digest, which is also the size of a single block of memory
+const BLOCK_SIZE: usize = 64;
+
+type MemoryBlock = [u8; BLOCK_SIZE];
+
+// A memory-hard hash in the spirit of scrypt's ROMix, with SHA-512 as the mixing function. It
+// fills `blocks` blocks of memory with a hash chain seeded by the input, then reads them back in
+// an order that depends on the data. Anyone who doesn't keep every block around has to recompute
+// them over and over, so a faster hash function alone doesn't help much: it needs fast memory too.
+// Returns the hex encoded result
+pub fn hash(input: &[u8], blocks: u32) -> String {
+    let blocks = cmp::max(blocks, 1) as usize;
+
+    let mut memory = Vec::with_capacity(blocks);
+    let mut state = sha512(input);
+    for _ in 0..blocks {
+        memory.push(state);
+        state = sha512(&state);
+    }
+
+    for _ in 0..blocks {
+        let mut mixed = memory[integerify(&state) % blocks];
+        for (byte, state_byte) in mixed.iter_mut().zip(state.iter()) {
+            *byte ^= *state_byte;
+        }
+        state = sha512(&mixed);
+    }
+
+    (&state[..]).to_hex()
+}
+
+fn sha512(data: &[u8]) -> MemoryBlock {
+    let mut block = [0; BLOCK_SIZE];
+    block.copy_from_slice(digest::digest(&digest::SHA512, data).as_ref());
+    block
+}
+
+// Interprets the first 8 bytes of the block as a little-endian integer to pick the next block
+fn integerify(block: &MemoryBlock) -> usize {
+    block[..8]
+        .iter()
+        .rev()
+        .fold(0u64, |value, &byte| (value << 8) | u64::from(byte)) as usize
+}
@@ -0,0 +1,423 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use uuid::Uuid;
+
+use super::{BlockchainError, ChainErrorKind, TransactionErrorKind};
+use super::transaction::Transaction;
+
+// The number of bytes the pending transactions can take up unless configured otherwise
+pub const DEFAULT_MEMPOOL_BYTES: usize = 64 * 1024;
+
+// A replacement has to pay at least this much more per 1000 bytes of its encoding than the
+// transaction it replaces. Every replacement is relayed again, so this makes sure the extra
+// traffic is paid for
+pub const REPLACEMENT_FEE_PER_KB: i64 = 10;
+
+// The transactions waiting to be included in a block. The pool is limited by the size of the
+// transactions' canonical encoding: once it's full, the transactions paying the lowest fee per
+// byte make room for better paying ones. Transactions of the same sender form a chain in the order
+// they arrived in, and a transaction is never included in a block before the ones it follows.
+// A pending transfer can be replaced by one with the same nonce that pays a higher fee, which
+// takes its place in the sender's chain
+#[derive(Debug, Clone)]
+pub struct Mempool {
+    // Kept in the order the transactions arrived in
+    transactions: Vec<Transaction>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl Mempool {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            transactions: vec![],
+            bytes: 0,
+            max_bytes,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    // The total size of the pending transactions
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    // The pending transactions in the order they arrived in
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    // The pending transaction with the given id
+    pub fn get(&self, id: &Uuid) -> Option<&Transaction> {
+        self.transactions.iter().find(|tx| tx.id() == id)
+    }
+
+    // The number of pending transfers of the sender
+    pub fn transfers_from(&self, sender: &str) -> u64 {
+        self.transactions
+            .iter()
+            .filter(|tx| tx.nonce().is_some() && tx.sender() == Some(sender))
+            .count() as u64
+    }
+
+    // Adds a transaction that's already been validated against the chain (as if the transaction
+    // it replaces wasn't there). If the pool is full, transactions with a lower fee rate are
+    // evicted together with the transactions that follow them from the same sender, but only if
+    // those pay a lower fee rate too. The sender's transactions that come before the new one are
+    // never evicted, since it can't be mined without them. The evicted and the replaced
+    // transactions are returned. Fails without changing the pool if the replacement isn't allowed
+    // or not enough space can be made
+    pub fn insert(&mut self, tx: Transaction) -> Result<Vec<Transaction>, BlockchainError> {
+        let size = tx.size();
+        let rate = fee_rate(&tx);
+        let replaced = self.transactions.iter().position(|x| x.replaces(&tx));
+        if let Some(i) = replaced {
+            validate_replacement(&self.transactions[i], &tx)?;
+        }
+
+        // Only strictly cheaper transactions are evicted, the cheapest and newest ones first.
+        // Evicting a transaction evicts the ones following it too, so it's only a candidate if
+        // none of those pays as much as the new one
+        let position = replaced.unwrap_or_else(|| self.transactions.len());
+        let mut candidates: Vec<usize> = (0..self.transactions.len())
+            .filter(|&i| {
+                let predecessor = i < position && tx.sender().is_some()
+                    && self.transactions[i].sender() == tx.sender();
+                Some(i) != replaced && !predecessor
+                    && self.chain_from(i)
+                        .iter()
+                        .all(|&j| fee_rate(&self.transactions[j]) < rate)
+            })
+            .collect();
+        candidates.sort_by(|&a, &b| {
+            compare_fee_rate(&self.transactions[a], &self.transactions[b]).then(b.cmp(&a))
+        });
+
+        let mut evicted = vec![false; self.transactions.len()];
+        let mut bytes = self.bytes;
+        if let Some(i) = replaced {
+            evicted[i] = true;
+            bytes -= self.transactions[i].size();
+        }
+        for i in candidates {
+            if bytes + size <= self.max_bytes {
+                break;
+            }
+            for j in self.chain_from(i) {
+                if !evicted[j] {
+                    evicted[j] = true;
+                    bytes -= self.transactions[j].size();
+                }
+            }
+        }
+
+        if bytes + size > self.max_bytes {
+            return Err(BlockchainError::chain(ChainErrorKind::MempoolFull));
+        }
+
+        // A replacement takes the place of the transaction it replaces, new ones go to the end
+        let mut kept = Vec::with_capacity(self.transactions.len() + 1);
+        let mut removed = vec![];
+        let mut tx = Some(tx);
+        for (i, (pending, evicted)) in self.transactions.drain(..).zip(evicted).enumerate() {
+            if Some(i) == replaced {
+                kept.extend(tx.take());
+            }
+            if evicted {
+                removed.push(pending);
+            } else {
+                kept.push(pending);
+            }
+        }
+        kept.extend(tx);
+        self.transactions = kept;
+        self.bytes = bytes + size;
+        Ok(removed)
+    }
+
+    // Removes the given transactions from the pool, usually because they were included in a block
+    pub fn remove(&mut self, transactions: &[Transaction]) {
+        self.transactions
+            .retain(|tx| !transactions.iter().any(|x| x.id() == tx.id()));
+        self.bytes = self.transactions.iter().map(|tx| tx.size()).sum();
+    }
+
+    // Removes the transactions that can't be included in the block at `height` or any later one,
+    // along with the later transactions of their senders which can't be mined without them. The
+    // removed transactions are returned
+    pub fn purge(&mut self, height: u64) -> Vec<Transaction> {
+        let mut expired = vec![false; self.transactions.len()];
+        for i in 0..self.transactions.len() {
+            if !expired[i] && self.transactions[i].is_expired(height) {
+                for j in self.chain_from(i) {
+                    expired[j] = true;
+                }
+            }
+        }
+
+        let mut removed = vec![];
+        let mut kept = vec![];
+        for (tx, expired) in self.transactions.drain(..).zip(expired) {
+            if expired {
+                removed.push(tx);
+            } else {
+                kept.push(tx);
+            }
+        }
+        self.transactions = kept;
+        self.bytes = self.transactions.iter().map(|tx| tx.size()).sum();
+        removed
+    }
+
+    // Empties the pool and returns its transactions in the order they arrived in
+    pub fn take(&mut self) -> Vec<Transaction> {
+        self.bytes = 0;
+        self.transactions.drain(..).collect()
+    }
+
+    // Picks at most `limit` transactions for the next block, the best paying ones first. A
+    // transaction is only considered once every earlier transaction of its sender is picked, and
+    // only if `fits` accepts it on top of the ones picked so far
+    pub fn select<F>(&self, limit: usize, mut fits: F) -> Vec<Transaction>
+    where
+        F: FnMut(&[Transaction], &Transaction) -> bool,
+    {
+        let mut order: Vec<usize> = (0..self.transactions.len()).collect();
+        order.sort_by(|&a, &b| {
+            compare_fee_rate(&self.transactions[b], &self.transactions[a]).then(a.cmp(&b))
+        });
+
+        let mut picked = vec![false; self.transactions.len()];
+        let mut selected: Vec<Transaction> = vec![];
+        while selected.len() < limit {
+            let next = order.iter().cloned().find(|&i| {
+                !picked[i] && self.previous(i).map_or(true, |j| picked[j])
+                    && fits(&selected, &self.transactions[i])
+            });
+            match next {
+                Some(i) => {
+                    picked[i] = true;
+                    selected.push(self.transactions[i].clone());
+                }
+                None => break,
+            }
+        }
+        selected
+    }
+
+    // The sender's transaction that arrived right before the one at `index`
+    fn previous(&self, index: usize) -> Option<usize> {
+        let sender = self.transactions[index].sender()?;
+        (0..index)
+            .rev()
+            .find(|&i| self.transactions[i].sender() == Some(sender))
+    }
+
+    // The transaction at `index` and every later transaction of the same sender
+    fn chain_from(&self, index: usize) -> Vec<usize> {
+        match self.transactions[index].sender() {
+            Some(sender) => (index..self.transactions.len())
+                .filter(|&i| self.transactions[i].sender() == Some(sender))
+                .collect(),
+            None => vec![index],
+        }
+    }
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Mempool::new(DEFAULT_MEMPOOL_BYTES)
+    }
+}
+
+// The pool is shared as a plain list of transactions
+impl Serialize for Mempool {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.transactions.serialize(serializer)
+    }
+}
+
+// Received transactions go through the same limit as ours, the ones that don't fit are dropped
+impl<'de> Deserialize<'de> for Mempool {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut mempool = Mempool::default();
+        for tx in Vec::<Transaction>::deserialize(deserializer)? {
+            let _ = mempool.insert(tx);
+        }
+        Ok(mempool)
+    }
+}
+
+// The replacement has to pay a higher fee rate, and at least `REPLACEMENT_FEE_PER_KB` more in
+// total for its own size
+fn validate_replacement(old: &Transaction, new: &Transaction) -> Result<(), BlockchainError> {
+    let increment = (new.size() as i64 * REPLACEMENT_FEE_PER_KB + 999) / 1000;
+    if new.fee() < old.fee() + increment || fee_rate(new) <= fee_rate(old) {
+        Err(BlockchainError::transaction(
+            *new.id(),
+            TransactionErrorKind::InsufficientReplacementFee,
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// The fee paid per byte of the transaction's encoding
+fn fee_rate(tx: &Transaction) -> f64 {
+    tx.fee() as f64 / tx.size() as f64
+}
+
+fn compare_fee_rate(a: &Transaction, b: &Transaction) -> Ordering {
+    fee_rate(a)
+        .partial_cmp(&fee_rate(b))
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::ToHex;
+    use ring::signature;
+    use untrusted;
+
+    use core::{BlockchainError, ChainErrorKind, Transaction, TransactionErrorKind};
+    use super::Mempool;
+
+    struct Sender {
+        address: String,
+        key_pair: signature::Ed25519KeyPair,
+    }
+
+    impl Sender {
+        fn new(seed: u8) -> Self {
+            let key_pair = signature::Ed25519KeyPair::from_seed_unchecked(untrusted::Input::from(
+                &[seed; 32],
+            )).expect("The seed has the right length");
+            Sender {
+                address: key_pair.public_key_bytes().to_hex(),
+                key_pair,
+            }
+        }
+
+        // Transfers all have the same size, so their fee rates compare like their fees
+        fn transfer(&self, fee: i64, nonce: u64) -> Transaction {
+            Transaction::transfer(&self.address, "recipient", 1, fee, nonce, &self.key_pair).into()
+        }
+    }
+
+    // A pool with room for exactly `count` transfers
+    fn mempool(count: usize) -> Mempool {
+        Mempool::new(Sender::new(0).transfer(0, 0).size() * count)
+    }
+
+    fn ids(transactions: &[Transaction]) -> Vec<String> {
+        transactions.iter().map(|tx| tx.id().to_string()).collect()
+    }
+
+    #[test]
+    fn evicts_the_cheapest_and_newest_first() {
+        let (a, b) = (Sender::new(1), Sender::new(2));
+        let mut mempool = mempool(2);
+        let a0 = a.transfer(1, 0);
+        let a1 = a.transfer(1, 1);
+        mempool.insert(a0.clone()).unwrap();
+        mempool.insert(a1.clone()).unwrap();
+
+        let b0 = b.transfer(5, 0);
+        let evicted = mempool.insert(b0.clone()).unwrap();
+        assert_eq!(ids(&evicted), ids(&[a1]));
+        assert_eq!(ids(mempool.transactions()), ids(&[a0, b0]));
+        assert_eq!(mempool.bytes(), mempool.max_bytes());
+    }
+
+    #[test]
+    fn evicts_the_sender_chain_that_follows() {
+        let (a, b) = (Sender::new(1), Sender::new(2));
+        let mut mempool = mempool(2);
+        let a0 = a.transfer(1, 0);
+        let a1 = a.transfer(2, 1);
+        mempool.insert(a0.clone()).unwrap();
+        mempool.insert(a1.clone()).unwrap();
+
+        // Evicting a0 alone would leave a1 without the transfer it follows
+        let evicted = mempool.insert(b.transfer(10, 0)).unwrap();
+        assert_eq!(ids(&evicted), ids(&[a0, a1]));
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn keeps_followers_paying_more_than_the_new_transaction() {
+        let (a, b) = (Sender::new(1), Sender::new(2));
+        let mut mempool = mempool(2);
+        mempool.insert(a.transfer(1, 0)).unwrap();
+        mempool.insert(a.transfer(100, 1)).unwrap();
+
+        match mempool.insert(b.transfer(2, 0)) {
+            Err(BlockchainError::Chain {
+                kind: ChainErrorKind::MempoolFull,
+            }) => {}
+            result => panic!("Expected the pool to be full, got {:?}", result),
+        }
+        assert_eq!(mempool.len(), 2);
+    }
+
+    #[test]
+    fn never_evicts_the_predecessors_of_the_new_transaction() {
+        let (a, b) = (Sender::new(1), Sender::new(2));
+        let mut mempool = mempool(2);
+        let a0 = a.transfer(1, 0);
+        let b0 = b.transfer(5, 0);
+        mempool.insert(a0.clone()).unwrap();
+        mempool.insert(b0.clone()).unwrap();
+
+        let a1 = a.transfer(10, 1);
+        let evicted = mempool.insert(a1.clone()).unwrap();
+        assert_eq!(ids(&evicted), ids(&[b0]));
+        assert_eq!(ids(mempool.transactions()), ids(&[a0, a1]));
+    }
+
+    #[test]
+    fn replacement_takes_the_place_of_the_replaced_transfer() {
+        let a = Sender::new(1);
+        let mut mempool = mempool(2);
+        let a0 = a.transfer(10, 0);
+        let a1 = a.transfer(10, 1);
+        mempool.insert(a0.clone()).unwrap();
+        mempool.insert(a1.clone()).unwrap();
+
+        let replacement = a.transfer(20, 0);
+        let replaced = mempool.insert(replacement.clone()).unwrap();
+        assert_eq!(ids(&replaced), ids(&[a0]));
+        assert_eq!(ids(mempool.transactions()), ids(&[replacement, a1]));
+        assert_eq!(mempool.bytes(), mempool.max_bytes());
+    }
+
+    #[test]
+    fn replacement_has_to_pay_for_its_size() {
+        let a = Sender::new(1);
+        let mut mempool = mempool(2);
+        let a0 = a.transfer(10, 0);
+        mempool.insert(a0.clone()).unwrap();
+
+        // The fee rate is higher, but not by enough to pay for relaying the replacement
+        match mempool.insert(a.transfer(11, 0)) {
+            Err(BlockchainError::Transaction {
+                kind: TransactionErrorKind::InsufficientReplacementFee,
+                ..
+            }) => {}
+            result => panic!("Expected the replacement to be rejected, got {:?}", result),
+        }
+        assert_eq!(ids(mempool.transactions()), ids(&[a0]));
+    }
+}
@@ -0,0 +1,167 @@
+use hex::ToHex;
+use ring::digest;
+
+use super::transaction::Transaction;
+
+// Which side of the path a sibling hash is on when climbing towards the root
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleStep {
+    pub hash: String,
+    pub side: Side,
+}
+
+// Proves that a transaction is part of a block without needing any of the other transactions:
+// hashing the transaction together with the siblings on its path to the root has to result in
+// the Merkle root stored in the block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub steps: Vec<MerkleStep>,
+}
+
+// Calculates the root of the Merkle tree built over the given leaf hashes. A node without a
+// sibling is carried up to the next level as is (instead of being paired with itself), so two
+// different transaction lists can never produce the same root. Empty lists have an empty root
+pub fn merkle_root(leaves: &[String]) -> String {
+    if leaves.is_empty() {
+        return String::new();
+    }
+
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level.remove(0)
+}
+
+impl MerkleProof {
+    // Builds the proof for the leaf at the given position. Returns None if it's out of bounds
+    pub fn build(leaves: &[String], mut index: usize) -> Option<Self> {
+        if index >= leaves.len() {
+            return None;
+        }
+
+        let mut steps = vec![];
+        let mut level = leaves.to_vec();
+        while level.len() > 1 {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                let side = if sibling < index { Side::Left } else { Side::Right };
+                steps.push(MerkleStep {
+                    hash: level[sibling].clone(),
+                    side,
+                });
+            }
+
+            level = next_level(&level);
+            index /= 2;
+        }
+
+        Some(MerkleProof { steps })
+    }
+
+    // Calculates the root this proof leads to when starting from the given leaf hash
+    pub fn root(&self, leaf: &str) -> String {
+        self.steps
+            .iter()
+            .fold(String::from(leaf), |hash, step| match step.side {
+                Side::Left => hash_pair(&step.hash, &hash),
+                Side::Right => hash_pair(&hash, &step.hash),
+            })
+    }
+
+    // Checks if the transaction is included in the block with the given Merkle root
+    pub fn verify(&self, transaction: &Transaction, root: &str) -> bool {
+        self.root(&transaction.hash()) == root
+    }
+}
+
+// Hashes every pair of nodes on a level into the level above it
+fn next_level(level: &[String]) -> Vec<String> {
+    level
+        .chunks(2)
+        .map(|pair| {
+            if pair.len() == 2 {
+                hash_pair(&pair[0], &pair[1])
+            } else {
+                pair[0].clone()
+            }
+        })
+        .collect()
+}
+
+// Hashes two child nodes into their parent
+fn hash_pair(left: &str, right: &str) -> String {
+    let mut context = digest::Context::new(&digest::SHA512);
+    context.update(left.as_bytes());
+    context.update(right.as_bytes());
+    context.finish().to_hex()
+}
+
+#[cfg(test)]
+mod tests {
+    use core::Transaction;
+    use super::{hash_pair, merkle_root, MerkleProof, Side};
+
+    fn leaves(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|i| Transaction::reward("miner", i as i64).hash())
+            .collect()
+    }
+
+    #[test]
+    fn every_leaf_proves_its_inclusion() {
+        for count in 1..10 {
+            let leaves = leaves(count);
+            let root = merkle_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = MerkleProof::build(&leaves, index).unwrap();
+                assert_eq!(proof.root(leaf), root, "leaf {} of {}", index, count);
+            }
+        }
+    }
+
+    #[test]
+    fn unpaired_node_is_carried_up() {
+        let leaves = leaves(3);
+        let root = hash_pair(&hash_pair(&leaves[0], &leaves[1]), &leaves[2]);
+        assert_eq!(merkle_root(&leaves), root);
+
+        // The last leaf has no sibling on the first level, so its proof only has one step
+        let proof = MerkleProof::build(&leaves, 2).unwrap();
+        assert_eq!(proof.steps.len(), 1);
+        assert_eq!(proof.steps[0].side, Side::Left);
+        assert_eq!(proof.root(&leaves[2]), root);
+
+        // Duplicating it would otherwise give a different list with the same root
+        let mut duplicated = leaves.clone();
+        duplicated.push(leaves[2].clone());
+        assert_ne!(merkle_root(&duplicated), root);
+    }
+
+    #[test]
+    fn proof_only_verifies_its_own_transaction() {
+        let transactions: Vec<Transaction> = (0..5)
+            .map(|i| Transaction::reward("miner", i))
+            .collect();
+        let leaves: Vec<String> = transactions.iter().map(|tx| tx.hash()).collect();
+        let root = merkle_root(&leaves);
+
+        let proof = MerkleProof::build(&leaves, 3).unwrap();
+        assert!(proof.verify(&transactions[3], &root));
+        assert!(!proof.verify(&transactions[2], &root));
+        assert!(!proof.verify(&transactions[4], &root));
+    }
+
+    #[test]
+    fn out_of_bounds_leaf_has_no_proof() {
+        assert_eq!(MerkleProof::build(&leaves(4), 4), None);
+        assert_eq!(MerkleProof::build(&[], 0), None);
+        assert_eq!(merkle_root(&[]), "");
+    }
+}
@@ -0,0 +1,208 @@
+use std::cmp;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono;
+use rand::{self, Rng};
+
+use super::block::Block;
+use super::header::BlockHeader;
+use super::params::PowAlgorithm;
+
+// Threads only publish their hash count and check whether they should stop this often
+const HASH_BATCH: u64 = 1024;
+
+// How often a long running search moves the header's timestamp forward
+const TIMESTAMP_REFRESH_SECS: u64 = 1;
+
+// Searches for proofs of work on a number of threads, each with its own part of the search space
+#[derive(Debug, Clone)]
+pub struct Miner {
+    threads: usize,
+}
+
+// A single search for a proof. It's shared between the mining threads and whoever started the
+// search, so it can be used to follow the progress or to cancel the job from the outside
+#[derive(Debug)]
+pub struct MiningJob {
+    cancelled: AtomicBool,
+    hashes: AtomicUsize,
+    started: Instant,
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct MiningProgress {
+    pub hashes: usize,
+    pub hash_rate: f64,
+    pub cancelled: bool,
+}
+
+impl Miner {
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: if threads == 0 { 1 } else { threads },
+        }
+    }
+
+    // Finds a proof for the block template and fills in the resulting hash. Returns None if the
+    // job was cancelled before a proof was found
+    pub fn mine(
+        &self,
+        mut block: Block,
+        algorithm: PowAlgorithm,
+        job: &Arc<MiningJob>,
+    ) -> Option<Block> {
+        self.seal(&block.header, algorithm, job).map(|(header, hash)| {
+            block.header = header;
+            block.hash = hash;
+            block
+        })
+    }
+
+    // Searches for a proof that satisfies the header's difficulty and returns the sealed header
+    // together with its hash. Every thread works on its own extra nonce so their search spaces
+    // never overlap, and all of them stop as soon as one finds a solution
+    pub fn seal(
+        &self,
+        header: &BlockHeader,
+        algorithm: PowAlgorithm,
+        job: &Arc<MiningJob>,
+    ) -> Option<(BlockHeader, String)> {
+        let (sender, receiver) = mpsc::channel();
+        let found = Arc::new(AtomicBool::new(false));
+
+        // Start at a random extra nonce to avoid wasting work across multiple nodes. It's kept
+        // within 32 bits so it stays exact for clients that read JSON numbers as doubles
+        let start = u64::from(rand::thread_rng().gen::<u32>());
+        let stride = self.threads as u64;
+
+        let handles: Vec<_> = (0..self.threads)
+            .map(|i| {
+                let mut header = header.clone();
+                header.proof = 0;
+                header.extra_nonce = start + i as u64;
+
+                let (sender, found, job) = (sender.clone(), found.clone(), job.clone());
+                thread::spawn(move || search(header, algorithm, stride, &sender, &found, &job))
+            })
+            .collect();
+
+        // Only the threads hold senders now, so if all of them stop because of a cancellation
+        // receiving fails
+        drop(sender);
+        let result = receiver.recv().ok();
+        found.store(true, Ordering::SeqCst);
+        for handle in handles {
+            let _ = handle.join();
+        }
+        result
+    }
+}
+
+impl Default for Miner {
+    fn default() -> Self {
+        Miner::new(4)
+    }
+}
+
+impl MiningJob {
+    pub fn new() -> Self {
+        Self {
+            cancelled: AtomicBool::new(false),
+            hashes: AtomicUsize::new(0),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    // The number of hashes tried so far by all threads
+    pub fn hashes(&self) -> usize {
+        self.hashes.load(Ordering::Relaxed)
+    }
+
+    // The average number of hashes per second since the job was started
+    pub fn hash_rate(&self) -> f64 {
+        let elapsed = self.started.elapsed();
+        let seconds = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9;
+        if seconds > 0.0 {
+            self.hashes() as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    pub fn progress(&self) -> MiningProgress {
+        MiningProgress {
+            hashes: self.hashes(),
+            hash_rate: self.hash_rate(),
+            cancelled: self.is_cancelled(),
+        }
+    }
+
+    fn record(&self, hashes: u64) {
+        self.hashes.fetch_add(hashes as usize, Ordering::Relaxed);
+    }
+}
+
+impl Default for MiningJob {
+    fn default() -> Self {
+        MiningJob::new()
+    }
+}
+
+// Tries every proof for the header's extra nonce, moving on to the next extra nonce that belongs
+// to this thread (every `stride`th one) when the proofs run out. Keeps going until a solution is
+// found, which is sent back to the miner, or until the search is stopped
+fn search(
+    mut header: BlockHeader,
+    algorithm: PowAlgorithm,
+    stride: u64,
+    sender: &Sender<(BlockHeader, String)>,
+    found: &AtomicBool,
+    job: &MiningJob,
+) {
+    let refresh = Duration::from_secs(TIMESTAMP_REFRESH_SECS);
+    let mut refreshed = Instant::now();
+    let mut batch = 0;
+    loop {
+        if batch == HASH_BATCH {
+            job.record(batch);
+            batch = 0;
+            if found.load(Ordering::SeqCst) || job.is_cancelled() {
+                return;
+            }
+
+            // Keep the timestamp close to the time the block is actually found. It never goes back,
+            // the template's timestamp might be ahead of our clock to follow the recent blocks
+            if refreshed.elapsed() >= refresh {
+                header.timestamp = cmp::max(header.timestamp, chrono::Utc::now().timestamp());
+                refreshed = Instant::now();
+            }
+        }
+
+        batch += 1;
+        if let Ok(hash) = header.try_hash(algorithm) {
+            job.record(batch);
+            found.store(true, Ordering::SeqCst);
+            let _ = sender.send((header, hash));
+            return;
+        }
+
+        if header.proof == u64::max_value() {
+            header.proof = 0;
+            header.extra_nonce = header.extra_nonce.wrapping_add(stride);
+        } else {
+            header.proof += 1;
+        }
+    }
+}
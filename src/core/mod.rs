@@ -1,32 +1,51 @@
-use hex::ToHex;
-use ring::digest;
+use hex::{FromHex, ToHex};
+use ring::{digest, signature};
 use serde::Serialize;
-use serde_json;
+use untrusted;
 
 pub mod block;
 pub mod chain;
+pub mod consensus;
+pub mod encoding;
 pub mod error;
+pub mod header;
+pub mod index;
+pub mod memory_hard;
+pub mod mempool;
+pub mod merkle;
+pub mod miner;
+pub mod params;
+pub mod spec;
+pub mod stake;
 pub mod transaction;
+pub mod tree;
+pub mod verifier;
 
 pub use self::block::Block;
-pub use self::chain::Blockchain;
+pub use self::chain::{BlockStatus, Blockchain};
+pub use self::consensus::{Consensus, Engine, ProofOfAuthority, ProofOfStake, ProofOfWork};
 pub use self::error::{BlockErrorKind, BlockchainError, ChainErrorKind, TransactionErrorKind};
-pub use self::transaction::{Reward, Transaction, Transfer};
+pub use self::header::{BlockHeader, HeaderChain};
+pub use self::mempool::Mempool;
+pub use self::merkle::MerkleProof;
+pub use self::miner::{Miner, MiningJob, MiningProgress};
+pub use self::params::{ChainParams, PowAlgorithm};
+pub use self::spec::{Allocation, ChainSpec};
+pub use self::stake::StakeLedger;
+pub use self::transaction::{Reward, Slash, Stake, StakeAction, Transaction, Transfer};
+pub use self::verifier::Verifier;
 
 pub trait Verify
 where
     Self: Serialize,
 {
-    // Converts the object to a byte representation which is the preferred input format for
-    // most cryptographic operations
+    // Converts the object to its canonical byte representation which is the input for every
+    // hash and signature. See `encoding` for the format
     fn to_bytes<'a, S>(&'a self) -> Vec<u8>
     where
         S: Verify + From<&'a Self>,
     {
-        let json = serde_json::to_string(&S::from(self))
-            .expect("Blockchain primitives must be able to be serialized into a byte vector");
-        let bytes = json.into_bytes();
-        bytes
+        encoding::encode(&S::from(self))
     }
 
     // Calculates the SHA512 hash of the byte representation of the given object after converting
@@ -46,3 +65,17 @@ where
     T: Serialize,
 {
 }
+
+// Checks an Ed25519 signature where both the public key and the signature are hex encoded.
+// Malformed keys and signatures are simply invalid
+fn verify_signature(public_key: &str, message: &[u8], signature: &str) -> bool {
+    match (Vec::from_hex(public_key), Vec::from_hex(signature)) {
+        (Ok(public_key), Ok(signature)) => signature::verify(
+            &signature::ED25519,
+            untrusted::Input::from(public_key.as_ref()),
+            untrusted::Input::from(message),
+            untrusted::Input::from(signature.as_ref()),
+        ).is_ok(),
+        _ => false,
+    }
+}
@@ -0,0 +1,114 @@
+use std::cmp;
+
+use super::header::BlockHeader;
+
+// The number of memory blocks the memory-hard proof of work uses by default, 64 KiB in total
+pub const DEFAULT_MEMORY_BLOCKS: u32 = 1024;
+
+// Difficulty is never retargeted above this: it's the full width of a SHA512 hash
+const MAX_DIFFICULTY: u32 = 512;
+
+// The rules a chain is created with. Every node on the same network needs to use the same
+// parameters, otherwise they will reject each other's blocks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainParams {
+    // The number of leading zero bits a block's hash needs to have for its proof to be accepted.
+    // This is only the starting value, the chain adjusts it as blocks get mined
+    pub difficulty: u32,
+
+    // The difficulty is recalculated every `retarget_interval` blocks. Zero disables retargeting
+    pub retarget_interval: u64,
+
+    // The number of seconds we'd like to pass between two consecutive blocks
+    pub target_block_time: i64,
+
+    // The hash function the difficulty is checked against
+    pub algorithm: PowAlgorithm,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowAlgorithm {
+    // The block hash itself (plain SHA-512) has to have enough leading zero bits. Cheap to verify,
+    // but specialised hardware computes it far faster than a regular computer
+    Sha512,
+
+    // A separate memory-hard hash of the header has to have enough leading zero bits, while the
+    // block is still identified by its SHA-512 hash. See `memory_hard::hash`
+    MemoryHard { blocks: u32 },
+}
+
+impl ChainParams {
+    pub fn with_difficulty(difficulty: u32) -> Self {
+        Self {
+            difficulty,
+            ..ChainParams::default()
+        }
+    }
+
+    // Memory-hard hashes are much slower to compute, so these chains usually need a lower
+    // difficulty than the default
+    pub fn memory_hard(difficulty: u32) -> Self {
+        Self {
+            difficulty,
+            algorithm: PowAlgorithm::MemoryHard {
+                blocks: DEFAULT_MEMORY_BLOCKS,
+            },
+            ..ChainParams::default()
+        }
+    }
+
+    // Calculates the difficulty the block following `blocks` has to be mined with. At every
+    // retarget the time it took to mine the last interval is compared to the time it should have
+    // taken: since one extra bit of difficulty doubles the expected work, we only step the
+    // difficulty once blocks arrive at least twice as fast (or slow) as the target
+    pub fn next_difficulty<H: AsRef<BlockHeader>>(&self, blocks: &[H]) -> u32 {
+        let previous = blocks.last().expect("Zero-length chains are invalid").as_ref();
+        // Positions are used instead of the stored indices so a malformed chain can't make us
+        // look outside of it
+        let next_index = blocks.len() as u64;
+
+        // The genesis block isn't mined so the first real block starts from the initial value
+        if next_index == 1 {
+            return self.difficulty;
+        }
+
+        if self.retarget_interval == 0 || next_index % self.retarget_interval != 0 {
+            return previous.difficulty;
+        }
+
+        // The window covers the last `retarget_interval` gaps between blocks, so an interval of 1
+        // still measures the gap before the tip. The genesis block has a fixed timestamp, so it
+        // can't be part of it
+        let first_index = cmp::max(1, next_index.saturating_sub(self.retarget_interval + 1));
+        let first = blocks[first_index as usize].as_ref();
+        let gaps = (next_index - 1 - first_index) as i64;
+        if gaps == 0 {
+            return previous.difficulty;
+        }
+
+        // Timestamps only have to beat the median of the recent blocks, so the window can still
+        // be negative or absurdly long. Saturating keeps that from overflowing
+        let expected = gaps.saturating_mul(self.target_block_time);
+        let actual = previous.timestamp.saturating_sub(first.timestamp);
+        if actual.saturating_mul(2) < expected {
+            cmp::min(previous.difficulty + 1, MAX_DIFFICULTY)
+        } else if actual > expected.saturating_mul(2) {
+            cmp::max(previous.difficulty, 2) - 1
+        } else {
+            previous.difficulty
+        }
+    }
+}
+
+impl Default for ChainParams {
+    // 16 bits is the same as the four leading zero hex digits we originally required
+    fn default() -> Self {
+        Self {
+            difficulty: 16,
+            retarget_interval: 10,
+            target_block_time: 30,
+            algorithm: PowAlgorithm::Sha512,
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use ring::digest;
+use serde_json;
+use uuid::Uuid;
+
+use super::Verify;
+use super::params::ChainParams;
+use super::transaction::{Reward, Transaction};
+
+// The reward for mining a block unless the chain spec says otherwise
+pub const DEFAULT_REWARD: i64 = 100;
+
+// The number of blocks after which the reward is halved by default
+pub const DEFAULT_HALVING_INTERVAL: u64 = 1000;
+
+// The number of transactions a block can hold on top of the miner's reward by default
+pub const DEFAULT_MAX_BLOCK_TRANSACTIONS: usize = 4;
+
+// Everything that defines a network. The genesis block is derived from the spec (its hash is the
+// hash of the spec), so nodes started with different specs never accept each other's chains
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainSpec {
+    pub name: String,
+    pub genesis_timestamp: i64,
+
+    // The amount the miner of a block is rewarded with before the first halving
+    pub reward: i64,
+
+    // The reward is halved every `halving_interval` blocks until it reaches zero. Zero disables
+    // halving, in which case the supply is unbounded
+    pub halving_interval: u64,
+
+    // The number of transactions a block can hold on top of the miner's reward
+    pub max_block_transactions: usize,
+
+    // The proof of work parameters, only used by chains that are secured by proof of work
+    pub params: ChainParams,
+
+    // Coins handed out by the genesis block, so the chain can start with funded accounts
+    pub allocations: Vec<Allocation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Allocation {
+    pub recipient: String,
+    pub amount: i64,
+}
+
+impl ChainSpec {
+    // Loads a spec from a JSON file. Missing fields take their default values. A spec with an
+    // allocation that isn't positive is rejected here, rather than when its genesis block fails
+    // validation
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let spec: ChainSpec = serde_json::from_reader(file)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        match spec.allocations.iter().find(|allocation| allocation.amount <= 0) {
+            Some(allocation) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("The allocation to {} isn't positive", allocation.recipient),
+            )),
+            None => Ok(spec),
+        }
+    }
+
+    // The hash of the canonical encoding of the spec, which becomes the hash of the genesis block
+    pub fn hash(&self) -> String {
+        self.calculate_hash::<&ChainSpec>()
+    }
+
+    // The reward for mining the block at the given height. The first block gets the full reward
+    pub fn block_reward(&self, height: u64) -> i64 {
+        if self.halving_interval == 0 || height == 0 {
+            return self.reward;
+        }
+
+        let halvings = (height - 1) / self.halving_interval;
+        if halvings >= 64 {
+            0
+        } else {
+            self.reward >> halvings
+        }
+    }
+
+    // The reward the miner of the block at the given height gets for a block with these
+    // transactions: the subsidy plus all of their fees. Returns None if that doesn't fit in an i64
+    pub fn miner_reward<'a, I>(&self, height: u64, transactions: I) -> Option<i64>
+    where
+        I: IntoIterator<Item = &'a Transaction>,
+    {
+        transactions
+            .into_iter()
+            .fold(Some(self.block_reward(height)), |total, tx| {
+                total.and_then(|total| total.checked_add(tx.fee()))
+            })
+    }
+
+    // The number of coins that will ever exist: the allocations plus every block reward until
+    // they run out. Returns None if the supply is unbounded or doesn't fit in an i64
+    pub fn max_supply(&self) -> Option<i64> {
+        if self.halving_interval == 0 && self.reward != 0 {
+            return None;
+        }
+
+        let allocated = self.allocations
+            .iter()
+            .fold(Some(0i64), |total, allocation| {
+                total.and_then(|total| total.checked_add(allocation.amount))
+            });
+
+        (0..64).fold(allocated, |total, halvings| {
+            let era = (self.reward >> halvings).checked_mul(self.halving_interval as i64);
+            total.and_then(|total| era.and_then(|era| total.checked_add(era)))
+        })
+    }
+
+    // The transactions of the genesis block: a reward for every allocation. Their ids are derived
+    // from the spec's hash and their position, so every node builds the exact same genesis block
+    pub fn genesis_transactions(&self) -> Vec<Transaction> {
+        let hash = self.hash();
+        self.allocations
+            .iter()
+            .enumerate()
+            .map(|(i, allocation)| {
+                let seed = digest::digest(&digest::SHA512, format!("{}{}", hash, i).as_bytes());
+                Transaction::Reward(Reward {
+                    id: Uuid::from_bytes(&seed.as_ref()[..16]).expect("Ids are 16 bytes long"),
+                    recipient: allocation.recipient.clone(),
+                    amount: allocation.amount,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        Self {
+            name: String::from("iridium"),
+            genesis_timestamp: 0,
+            reward: DEFAULT_REWARD,
+            halving_interval: DEFAULT_HALVING_INTERVAL,
+            max_block_transactions: DEFAULT_MAX_BLOCK_TRANSACTIONS,
+            params: ChainParams::default(),
+            allocations: vec![],
+        }
+    }
+}
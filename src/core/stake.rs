@@ -0,0 +1,229 @@
+use std::cmp;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use ring::digest;
+use uuid::Uuid;
+
+use super::{BlockchainError, TransactionErrorKind};
+use super::transaction::{StakeAction, Transaction};
+
+// Unlocked stake is only paid out at the end of the block this many blocks after the unlock. Until
+// then it can still be slashed, so a validator can't dodge a slash by unlocking its stake as soon
+// as it misbehaves
+pub const UNBONDING_BLOCKS: u64 = 100;
+
+// Locked stake only counts when choosing validators from the block this many blocks after the
+// lock. The seed of every height is known in advance, so otherwise a validator could lock just the
+// right amount right before the height it wants to be chosen at
+pub const ACTIVATION_BLOCKS: u64 = 100;
+
+// Stake on its way in or out: a lock that isn't active yet or an unlock that wasn't paid out yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pending {
+    validator: String,
+    amount: i64,
+}
+
+// The stake a slash took away: the locked stake, the locks that weren't active yet and the
+// unlocks that weren't paid out yet
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Slashed {
+    locked: i64,
+    activating: BTreeMap<(u64, Uuid), Pending>,
+    unlocked: BTreeMap<(u64, Uuid), Pending>,
+}
+
+// The stake every validator has locked, built by replaying the stake changes of a chain in order.
+// Validators are kept sorted so every node walks them in the same order when selecting one
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StakeLedger {
+    stakes: BTreeMap<String, i64>,
+    // Every lock keyed by the index of the first block it counts for and the id of the lock.
+    // Active locks are kept, so rolling back to before the activation makes them inactive again
+    activating: BTreeMap<(u64, Uuid), Pending>,
+    // Every unlock keyed by the index of the block that pays it out and the id of the unlock.
+    // Unlocks that were paid out are kept, so rolling that block back can take the payout back
+    unlocked: BTreeMap<(u64, Uuid), Pending>,
+    // The stake every applied slash took away, so it can be given back when the slash is reverted
+    slashed: HashMap<Uuid, Slashed>,
+    // The offences that were already punished, as the validator and the height it double signed
+    // at, so the same evidence can't be submitted again to wipe stake locked after the offence
+    offences: HashSet<(String, u64)>,
+}
+
+impl StakeLedger {
+    pub fn new() -> Self {
+        StakeLedger::default()
+    }
+
+    // Applies the effect on the stake of a transaction in the block with the given index.
+    // Validators can only unlock what they have locked, and a slashed validator loses everything
+    // it had locked up to that point as well as everything it unlocked that wasn't paid out yet
+    pub fn apply(&mut self, tx: &Transaction, index: u64) -> Result<(), BlockchainError> {
+        match *tx {
+            Transaction::Stake(ref stake) => {
+                let locked = self.stake(&stake.validator);
+                let pending = Pending {
+                    validator: stake.validator.clone(),
+                    amount: stake.amount,
+                };
+                let locked = match stake.action {
+                    StakeAction::Lock => {
+                        self.activating
+                            .insert((index + ACTIVATION_BLOCKS, stake.id), pending);
+                        locked + stake.amount
+                    }
+                    StakeAction::Unlock if locked >= stake.amount => {
+                        self.unlocked
+                            .insert((index + UNBONDING_BLOCKS, stake.id), pending);
+                        locked - stake.amount
+                    }
+                    StakeAction::Unlock => {
+                        return Err(BlockchainError::transaction(
+                            stake.id,
+                            TransactionErrorKind::InsufficientStake,
+                        ))
+                    }
+                };
+                self.set(&stake.validator, locked);
+            }
+            Transaction::Slash(ref slash) => {
+                if !self.offences.insert((slash.validator.clone(), slash.first.index)) {
+                    return Err(BlockchainError::transaction(
+                        slash.id,
+                        TransactionErrorKind::DuplicateEvidence,
+                    ));
+                }
+                let slashed = Slashed {
+                    locked: self.stake(&slash.validator),
+                    activating: take_pending(&mut self.activating, index + 1, &slash.validator),
+                    unlocked: take_pending(&mut self.unlocked, index, &slash.validator),
+                };
+                self.slashed.insert(slash.id, slashed);
+                self.set(&slash.validator, 0);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Undoes `apply` for the last transaction applied to the ledger, used when its block is rolled
+    // back
+    pub fn revert(&mut self, tx: &Transaction, index: u64) {
+        match *tx {
+            Transaction::Stake(ref stake) => {
+                let locked = self.stake(&stake.validator);
+                let locked = match stake.action {
+                    StakeAction::Lock => {
+                        self.activating.remove(&(index + ACTIVATION_BLOCKS, stake.id));
+                        locked - stake.amount
+                    }
+                    StakeAction::Unlock => {
+                        self.unlocked.remove(&(index + UNBONDING_BLOCKS, stake.id));
+                        locked + stake.amount
+                    }
+                };
+                self.set(&stake.validator, locked);
+            }
+            Transaction::Slash(ref slash) => {
+                self.offences.remove(&(slash.validator.clone(), slash.first.index));
+                let slashed = self.slashed.remove(&slash.id).unwrap_or_default();
+                self.activating.extend(slashed.activating);
+                self.unlocked.extend(slashed.unlocked);
+                self.set(&slash.validator, slashed.locked);
+            }
+            _ => {}
+        }
+    }
+
+    // The unlocked stake paid out at the end of the block with the given index, as the validator
+    // and the amount it gets back
+    pub fn payouts(&self, index: u64) -> Vec<(&str, i64)> {
+        self.unlocked
+            .range((index, Uuid::nil())..(index + 1, Uuid::nil()))
+            .map(|(_, x)| (x.validator.as_ref(), x.amount))
+            .collect()
+    }
+
+    pub fn stake(&self, validator: &str) -> i64 {
+        self.stakes.get(validator).cloned().unwrap_or(0)
+    }
+
+    // Every validator that has stake locked
+    pub fn validators(&self) -> Vec<&str> {
+        self.stakes.keys().map(|validator| validator.as_ref()).collect()
+    }
+
+    pub fn total(&self) -> i64 {
+        self.stakes.values().sum()
+    }
+
+    // The stake of every validator that counts when choosing the creator of the block with the
+    // given index, which leaves out the locks that aren't active yet. A validator that unlocked
+    // part of its stake before its lock became active can end up with less, never more
+    pub fn active(&self, index: u64) -> Vec<(&str, i64)> {
+        let mut inactive: HashMap<&str, i64> = HashMap::new();
+        for lock in self.activating.range((index + 1, Uuid::nil())..).map(|(_, x)| x) {
+            *inactive.entry(&lock.validator).or_insert(0) += lock.amount;
+        }
+
+        self.stakes
+            .iter()
+            .map(|(validator, &stake)| {
+                let inactive = inactive.get(validator.as_str()).cloned().unwrap_or(0);
+                (validator.as_ref(), cmp::max(0, stake - inactive))
+            })
+            .filter(|&(_, stake)| stake > 0)
+            .collect()
+    }
+
+    // Picks the creator of the block with the given index with a probability proportional to the
+    // validators' active stake. The seed decides the outcome, so every node that uses the same
+    // seed picks the same validator. Returns None if nobody has any active stake
+    pub fn select(&self, seed: &str, index: u64) -> Option<&str> {
+        let active = self.active(index);
+        let total: i64 = active.iter().map(|&(_, stake)| stake).sum();
+        if total <= 0 {
+            return None;
+        }
+
+        // The first 8 bytes of the seed's hash are plenty to spread the choice over any stake
+        let hash = digest::digest(&digest::SHA512, seed.as_bytes());
+        let target = hash.as_ref()[..8]
+            .iter()
+            .fold(0u64, |value, &byte| (value << 8) | u64::from(byte)) % total as u64;
+
+        let mut covered = 0;
+        for (validator, stake) in active {
+            covered += stake as u64;
+            if target < covered {
+                return Some(validator);
+            }
+        }
+        None
+    }
+
+    fn set(&mut self, validator: &str, stake: i64) {
+        if stake == 0 {
+            self.stakes.remove(validator);
+        } else {
+            self.stakes.insert(validator.to_owned(), stake);
+        }
+    }
+}
+
+// Removes and returns the validator's pending stake from the given index on
+fn take_pending(
+    pending: &mut BTreeMap<(u64, Uuid), Pending>,
+    index: u64,
+    validator: &str,
+) -> BTreeMap<(u64, Uuid), Pending> {
+    let keys: Vec<_> = pending
+        .range((index, Uuid::nil())..)
+        .filter(|&(_, x)| x.validator == validator)
+        .map(|(&key, _)| key)
+        .collect();
+    keys.into_iter()
+        .filter_map(|key| pending.remove(&key).map(|x| (key, x)))
+        .collect()
+}
@@ -1,18 +1,25 @@
-use hex::{FromHex, ToHex};
+use hex::ToHex;
 use ring::signature;
-use untrusted;
 use uuid::Uuid;
 
 use core::Verify;
 
 use super::{BlockchainError, TransactionErrorKind};
-
-pub const MINER_REWARD: i64 = 100;
+use super::header::BlockHeader;
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Transfer {
     pub id: Uuid,
     pub amount: i64,
+    // Paid by the sender on top of the amount and claimed by the miner of the block
+    pub fee: i64,
+    // The number of transfers the sender made before this one. Transfers are applied in the order
+    // of their nonces, and a transfer can't be replayed since its nonce is used up
+    pub nonce: u64,
+    // The transfer can only be included in blocks with an index above `valid_after` and at most
+    // `valid_until`. Either end of the window can be left open
+    pub valid_after: Option<u64>,
+    pub valid_until: Option<u64>,
     pub sender: String,
     pub recipient: String,
     pub signature: String,
@@ -25,19 +32,88 @@ pub struct Reward {
     pub amount: i64,
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum StakeAction {
+    Lock,
+    Unlock,
+}
+
+// Locks part of a validator's balance as stake or unlocks stake it locked earlier. Only locked
+// coins count when proof of stake picks who creates the next block
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Stake {
+    pub id: Uuid,
+    pub validator: String,
+    pub action: StakeAction,
+    pub amount: i64,
+    pub signature: String,
+}
+
+// Evidence that a validator signed two different blocks at the same height. Anyone can submit
+// it, and once it's in the chain the validator loses all of its locked stake. Every offence can
+// only be punished once
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Slash {
+    pub id: Uuid,
+    pub validator: String,
+    pub first: BlockHeader,
+    pub second: BlockHeader,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Transaction {
     Transfer(Transfer),
     Reward(Reward),
+    Stake(Stake),
+    Slash(Slash),
 }
 
+// Transactions are hashed as a whole, signature included, when building a block's Merkle tree
+#[derive(Debug, Serialize)]
+enum HashedTransaction<'a> {
+    Transfer(VerifiedTransfer<'a>, &'a str),
+    Reward(VerifiedReward<'a>),
+    Stake(VerifiedStake<'a>, &'a str),
+    Slash(HashedSlash<'a>),
+}
+
+// The signed part of a transfer. The id is encoded as its 16 raw bytes
 #[derive(Debug, Serialize)]
 struct VerifiedTransfer<'a> {
-    id: Uuid,
+    id: &'a [u8; 16],
     sender: &'a str,
     recipient: &'a str,
     amount: i64,
+    fee: i64,
+    nonce: u64,
+    valid_after: Option<u64>,
+    valid_until: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct VerifiedReward<'a> {
+    id: &'a [u8; 16],
+    recipient: &'a str,
+    amount: i64,
+}
+
+// The signed part of a stake change
+#[derive(Debug, Serialize)]
+struct VerifiedStake<'a> {
+    id: &'a [u8; 16],
+    validator: &'a str,
+    action: StakeAction,
+    amount: i64,
+}
+
+// The evidence headers are hashed with their signatures, since those are what prove the offence
+#[derive(Debug, Serialize)]
+struct HashedSlash<'a> {
+    id: &'a [u8; 16],
+    validator: &'a str,
+    first: &'a BlockHeader,
+    second: &'a BlockHeader,
 }
 
 impl Transaction {
@@ -46,6 +122,8 @@ impl Transaction {
         sender: S,
         recipient: R,
         amount: i64,
+        fee: i64,
+        nonce: u64,
         key_pair: &signature::Ed25519KeyPair,
     ) -> Transfer {
         // We create the transfer with a dummy signature first so we can reuse Verify::to_bytes
@@ -55,6 +133,10 @@ impl Transaction {
             sender: String::from(sender.as_ref()),
             recipient: String::from(recipient.as_ref()),
             amount,
+            fee,
+            nonce,
+            valid_after: None,
+            valid_until: None,
             signature: String::new(),
         };
 
@@ -70,12 +152,32 @@ impl Transaction {
         transfer
     }
 
+    // Creates a new stake change, signed by the validator's private key
+    pub fn stake<V: AsRef<str>>(
+        validator: V,
+        action: StakeAction,
+        amount: i64,
+        key_pair: &signature::Ed25519KeyPair,
+    ) -> Stake {
+        let mut stake = Stake {
+            id: Uuid::new_v4(),
+            validator: String::from(validator.as_ref()),
+            action,
+            amount,
+            signature: String::new(),
+        };
+
+        let message = stake.to_bytes::<VerifiedStake>();
+        stake.signature = key_pair.sign(message.as_ref()).to_hex();
+        stake
+    }
+
     // Creates a new miner reward transaction. Rewards aren't signed
-    pub fn reward<R: AsRef<str>>(recipient: R) -> Self {
+    pub fn reward<R: AsRef<str>>(recipient: R, amount: i64) -> Self {
         Transaction::Reward(Reward {
             id: Uuid::new_v4(),
             recipient: String::from(recipient.as_ref()),
-            amount: MINER_REWARD,
+            amount,
         })
     }
 
@@ -83,20 +185,26 @@ impl Transaction {
         match *self {
             Transaction::Transfer(ref transfer) => &transfer.id,
             Transaction::Reward(ref reward) => &reward.id,
+            Transaction::Stake(ref stake) => &stake.id,
+            Transaction::Slash(ref slash) => &slash.id,
         }
     }
 
+    // The account that signed the transaction and whose balance it might reduce
     pub fn sender(&self) -> Option<&str> {
         match *self {
             Transaction::Transfer(ref transfer) => Some(transfer.sender.as_ref()),
-            Transaction::Reward(_) => None,
+            Transaction::Stake(ref stake) => Some(stake.validator.as_ref()),
+            Transaction::Reward(_) | Transaction::Slash(_) => None,
         }
     }
 
-    pub fn recipient(&self) -> &str {
+    // The account that receives the transaction's amount
+    pub fn recipient(&self) -> Option<&str> {
         match *self {
-            Transaction::Transfer(ref transfer) => transfer.recipient.as_ref(),
-            Transaction::Reward(ref reward) => reward.recipient.as_ref(),
+            Transaction::Transfer(ref transfer) => Some(transfer.recipient.as_ref()),
+            Transaction::Reward(ref reward) => Some(reward.recipient.as_ref()),
+            Transaction::Stake(_) | Transaction::Slash(_) => None,
         }
     }
 
@@ -104,33 +212,144 @@ impl Transaction {
         match *self {
             Transaction::Transfer(ref transfer) => transfer.amount,
             Transaction::Reward(ref reward) => reward.amount,
+            Transaction::Stake(ref stake) => stake.amount,
+            Transaction::Slash(_) => 0,
+        }
+    }
+
+    // The position of the transaction among its sender's transfers
+    pub fn nonce(&self) -> Option<u64> {
+        match *self {
+            Transaction::Transfer(ref transfer) => Some(transfer.nonce),
+            _ => None,
+        }
+    }
+
+    // Whether the two transactions are transfers of the same sender with the same nonce, in which
+    // case only one of them can ever be part of the chain
+    pub fn replaces(&self, other: &Transaction) -> bool {
+        self.nonce().is_some() && self.nonce() == other.nonce() && self.sender() == other.sender()
+    }
+
+    // The part of the transaction that goes to the miner of its block
+    pub fn fee(&self) -> i64 {
+        match *self {
+            Transaction::Transfer(ref transfer) => transfer.fee,
+            _ => 0,
+        }
+    }
+
+    // Checks whether the transaction can be included in the block with the given index. Only
+    // transfers have a validity window, everything else is valid at any height
+    pub fn validate_height(&self, height: u64) -> Result<(), BlockchainError> {
+        match *self {
+            Transaction::Transfer(ref transfer) => transfer.validate_height(height),
+            _ => Ok(()),
         }
     }
 
+    pub fn is_expired(&self, height: u64) -> bool {
+        match *self {
+            Transaction::Transfer(ref transfer) => transfer.is_expired(height),
+            _ => false,
+        }
+    }
+
+    pub fn hash(&self) -> String {
+        self.calculate_hash::<HashedTransaction>()
+    }
+
+    // The size of the transaction's canonical encoding, which is what the mempool is limited by
+    pub fn size(&self) -> usize {
+        self.to_bytes::<HashedTransaction>().len()
+    }
+
     // Calculates the effects of this transaction on the provided address's overall balance:
-    // 1. If the address is the sender then it's the negative amount
+    // 1. If the address is the sender then it's the negative amount, fee included. If it's the
+    //    recipient as well the amount comes straight back, so only the fee is lost
     // 2. If the address is the recipient then it's the positive amount
+    // 3. If the address is the validator then locking is negative. Unlocking doesn't change the
+    //    balance right away, the stake is paid out when it's done unbonding (see `StakeLedger`)
     pub fn balance_change(&self, address: &str) -> i64 {
         match *self {
-            Transaction::Transfer(ref transfer) if transfer.sender == address => -transfer.amount,
+            Transaction::Transfer(ref transfer) if transfer.sender == address => {
+                if transfer.recipient == address {
+                    -transfer.fee
+                } else {
+                    -(transfer.amount + transfer.fee)
+                }
+            }
             Transaction::Transfer(ref transfer) if transfer.recipient == address => transfer.amount,
             Transaction::Reward(ref reward) if reward.recipient == address => reward.amount,
+            Transaction::Stake(ref stake)
+                if stake.validator == address && stake.action == StakeAction::Lock =>
+            {
+                -stake.amount
+            }
             _ => 0,
         }
     }
 
     // Validates the transaction, by checking if:
-    // 1. its signature is valid if it's a transfer
-    // 2. the amount equals to the miner reward if it's a reward
+    // 1. its signature is valid if it's a transfer or a stake change
+    // 2. the evidence proves the offence if it's a slash
+    // The amount of a reward depends on the chain spec, so rewards are checked with their block
     pub fn validate(&self) -> Result<(), BlockchainError> {
         match *self {
             Transaction::Transfer(ref transfer) => transfer.validate(),
-            Transaction::Reward(ref reward) => reward.validate(),
+            Transaction::Reward(_) => Ok(()),
+            Transaction::Stake(ref stake) => stake.validate(),
+            Transaction::Slash(ref slash) => slash.validate(),
         }
     }
 }
 
 impl Transfer {
+    // Creates a replacement for this transfer that pays the given fee instead. It keeps the nonce,
+    // which is what tells the mempool to replace the pending transfer
+    pub fn with_fee(&self, fee: i64, key_pair: &signature::Ed25519KeyPair) -> Transfer {
+        Transfer {
+            fee,
+            ..self.clone()
+        }.signed(key_pair)
+    }
+
+    // Creates a copy of this transfer that can only be included in blocks within the given window
+    pub fn with_validity(
+        &self,
+        valid_after: Option<u64>,
+        valid_until: Option<u64>,
+        key_pair: &signature::Ed25519KeyPair,
+    ) -> Transfer {
+        Transfer {
+            valid_after,
+            valid_until,
+            ..self.clone()
+        }.signed(key_pair)
+    }
+
+    // Whether the transfer can't be included in the block with the given index or any later one
+    pub fn is_expired(&self, height: u64) -> bool {
+        self.valid_until.map_or(false, |until| height > until)
+    }
+
+    // Whether the transfer can be included in the block with the given index
+    pub fn validate_height(&self, height: u64) -> Result<(), BlockchainError> {
+        if self.valid_after.map_or(false, |after| height <= after) {
+            Err(BlockchainError::transaction(
+                self.id,
+                TransactionErrorKind::NotYetValid,
+            ))
+        } else if self.is_expired(height) {
+            Err(BlockchainError::transaction(
+                self.id,
+                TransactionErrorKind::Expired,
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn validate(&self) -> Result<(), BlockchainError> {
         if self.amount <= 0 {
             return Err(BlockchainError::transaction(
@@ -139,27 +358,80 @@ impl Transfer {
             ));
         }
 
-        let public_key_bytes = Vec::from_hex(&self.sender).unwrap();
-        let signature_bytes = Vec::from_hex(&self.signature).unwrap();
+        // The sender pays the amount and the fee together, so their sum has to fit too
+        if self.fee < 0 || self.amount.checked_add(self.fee).is_none() {
+            return Err(BlockchainError::transaction(
+                self.id,
+                TransactionErrorKind::InvalidFee,
+            ));
+        }
+
+        let message = self.to_bytes::<VerifiedTransfer>();
+        if super::verify_signature(&self.sender, &message, &self.signature) {
+            Ok(())
+        } else {
+            Err(BlockchainError::transaction(
+                self.id,
+                TransactionErrorKind::InvalidSignature,
+            ))
+        }
+    }
 
-        let public_key = untrusted::Input::from(public_key_bytes.as_ref());
-        let signature = untrusted::Input::from(signature_bytes.as_ref());
+    fn signed(mut self, key_pair: &signature::Ed25519KeyPair) -> Transfer {
         let message = self.to_bytes::<VerifiedTransfer>();
-        signature::verify(
-            &signature::ED25519,
-            public_key,
-            untrusted::Input::from(message.as_ref()),
-            signature,
-        ).map_err(|_| BlockchainError::transaction(self.id, TransactionErrorKind::InvalidSignature))
+        self.signature = key_pair.sign(message.as_ref()).to_hex();
+        self
     }
 }
 
-impl Reward {
-    // Checks if the transaction amount matches the current miner reward.
-    // NOTE: this is not very future-proof, it basically ties any future miner reward changes
-    // to a client update
+impl Stake {
+    // Whether the validator actually has the stake to unlock depends on the chain, this only
+    // checks the transaction itself
     pub fn validate(&self) -> Result<(), BlockchainError> {
-        if self.amount != MINER_REWARD {
+        if self.amount <= 0 {
+            return Err(BlockchainError::transaction(
+                self.id,
+                TransactionErrorKind::InvalidAmount,
+            ));
+        }
+
+        let message = self.to_bytes::<VerifiedStake>();
+        if super::verify_signature(&self.validator, &message, &self.signature) {
+            Ok(())
+        } else {
+            Err(BlockchainError::transaction(
+                self.id,
+                TransactionErrorKind::InvalidSignature,
+            ))
+        }
+    }
+}
+
+impl Slash {
+    // The evidence holds if both headers are at the same height, are different and were both
+    // signed by the validator. They don't need to share a parent: a validator signing blocks on
+    // two different branches at once is just as much of an offence
+    pub fn validate(&self) -> Result<(), BlockchainError> {
+        let (first, second) = (&self.first, &self.second);
+        if first.index == second.index && first.hash() != second.hash()
+            && first.verify_signature(&self.validator)
+            && second.verify_signature(&self.validator)
+        {
+            Ok(())
+        } else {
+            Err(BlockchainError::transaction(
+                self.id,
+                TransactionErrorKind::InvalidEvidence,
+            ))
+        }
+    }
+}
+
+impl Reward {
+    // Checks if the transaction amount matches the miner reward of the block it's in: the subsidy
+    // for the block's height (see `ChainSpec::block_reward`) plus the fees of its transactions
+    pub fn validate(&self, reward: i64) -> Result<(), BlockchainError> {
+        if self.amount != reward {
             Err(BlockchainError::transaction(
                 self.id,
                 TransactionErrorKind::MismatchedMinerReward,
@@ -182,13 +454,64 @@ impl From<Reward> for Transaction {
     }
 }
 
+impl From<Stake> for Transaction {
+    fn from(stake: Stake) -> Self {
+        Transaction::Stake(stake)
+    }
+}
+
+impl From<Slash> for Transaction {
+    fn from(slash: Slash) -> Self {
+        Transaction::Slash(slash)
+    }
+}
+
+impl<'a> From<&'a Transaction> for HashedTransaction<'a> {
+    fn from(transaction: &Transaction) -> HashedTransaction {
+        match *transaction {
+            Transaction::Transfer(ref transfer) => {
+                HashedTransaction::Transfer(VerifiedTransfer::from(transfer), &transfer.signature)
+            }
+            Transaction::Reward(ref reward) => HashedTransaction::Reward(VerifiedReward {
+                id: reward.id.as_bytes(),
+                recipient: &reward.recipient,
+                amount: reward.amount,
+            }),
+            Transaction::Stake(ref stake) => {
+                HashedTransaction::Stake(VerifiedStake::from(stake), &stake.signature)
+            }
+            Transaction::Slash(ref slash) => HashedTransaction::Slash(HashedSlash {
+                id: slash.id.as_bytes(),
+                validator: &slash.validator,
+                first: &slash.first,
+                second: &slash.second,
+            }),
+        }
+    }
+}
+
 impl<'a> From<&'a Transfer> for VerifiedTransfer<'a> {
     fn from(transfer: &Transfer) -> VerifiedTransfer {
         VerifiedTransfer {
-            id: transfer.id,
+            id: transfer.id.as_bytes(),
             sender: &transfer.sender,
             recipient: &transfer.recipient,
             amount: transfer.amount,
+            fee: transfer.fee,
+            nonce: transfer.nonce,
+            valid_after: transfer.valid_after,
+            valid_until: transfer.valid_until,
+        }
+    }
+}
+
+impl<'a> From<&'a Stake> for VerifiedStake<'a> {
+    fn from(stake: &Stake) -> VerifiedStake {
+        VerifiedStake {
+            id: stake.id.as_bytes(),
+            validator: &stake.validator,
+            action: stake.action,
+            amount: stake.amount,
         }
     }
 }
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+use super::block::Block;
+
+// The maximum number of blocks we are willing to hold on to without knowing their parent
+const ORPHAN_LIMIT: usize = 64;
+
+// Side branch blocks this far below the tip of the best chain are dropped, since switching to
+// their branch would mean rolling back more blocks than any honest fork would
+pub const MAX_FORK_DEPTH: u64 = 100;
+
+// Holds every block we know about that isn't part of the best chain: blocks of competing branches
+// (which might overtake the best chain later) and orphans whose parent hasn't arrived yet
+#[derive(Debug, Clone, Default)]
+pub struct BlockTree {
+    // Side branch blocks keyed by their hash
+    blocks: HashMap<String, Block>,
+
+    // Orphan blocks in the order they arrived in
+    orphans: Vec<Block>,
+}
+
+impl BlockTree {
+    pub fn new() -> Self {
+        BlockTree::default()
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&Block> {
+        self.blocks.get(hash)
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    pub fn insert(&mut self, block: Block) {
+        self.blocks.insert(block.hash.clone(), block);
+    }
+
+    pub fn remove(&mut self, hash: &str) -> Option<Block> {
+        self.blocks.remove(hash)
+    }
+
+    pub fn is_orphan(&self, block: &Block) -> bool {
+        self.orphans.iter().any(|x| x.hash == block.hash)
+    }
+
+    // Stores a block until its parent arrives. If the orphan pool is full the oldest orphan makes
+    // room for it, so blocks whose parent never shows up can't fill the pool for good
+    pub fn add_orphan(&mut self, block: Block) {
+        if self.orphans.len() >= ORPHAN_LIMIT {
+            self.orphans.remove(0);
+        }
+        self.orphans.push(block);
+    }
+
+    // Removes and returns every orphan that was waiting for the block with the given hash
+    pub fn take_orphans(&mut self, parent: &str) -> Vec<Block> {
+        let (children, orphans) = self.orphans.drain(..).partition(|block| {
+            block.header.previous_hash.as_ref().map(|x| x.as_ref()) == Some(parent)
+        });
+        self.orphans = orphans;
+        children
+    }
+
+    // Drops the side branch blocks below the given index
+    pub fn prune(&mut self, min_index: u64) {
+        self.blocks.retain(|_, block| block.index() >= min_index);
+    }
+}
@@ -0,0 +1,86 @@
+use std::cmp;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crossbeam;
+
+use super::block::Block;
+use super::error::BlockchainError;
+use super::transaction::Transaction;
+
+// The threads take this many transactions at a time
+const BATCH_SIZE: usize = 64;
+
+// Runs the checks every transaction can do on its own, most importantly verifying its signature,
+// on a number of threads. These checks don't depend on each other or on the state of the chain,
+// so unlike the rest of the validation they can happen in any order
+#[derive(Debug, Clone)]
+pub struct Verifier {
+    threads: usize,
+}
+
+impl Verifier {
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: if threads == 0 { 1 } else { threads },
+        }
+    }
+
+    // Validates every transaction of the blocks. The results are returned for each block in the
+    // order of its transactions, so the caller can still report the first failure of the chain no
+    // matter which thread found it. The threads borrow the transactions from the blocks, so
+    // nothing is copied
+    pub fn validate_transactions(&self, blocks: &[Block]) -> Vec<Vec<Result<(), BlockchainError>>> {
+        let transactions: Vec<&Transaction> = blocks
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .collect();
+        let batches = (transactions.len() + BATCH_SIZE - 1) / BATCH_SIZE;
+        let next = AtomicUsize::new(0);
+
+        let mut validated: Vec<_> = crossbeam::scope(|scope| {
+            let handles: Vec<_> = (0..cmp::min(self.threads, batches))
+                .map(|_| scope.spawn(|| validate_batches(&transactions, &next)))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join())
+                .collect()
+        });
+        validated.sort_by_key(|&(batch, _)| batch);
+
+        let mut results = validated.into_iter().flat_map(|(_, results)| results);
+        blocks
+            .iter()
+            .map(|block| results.by_ref().take(block.transactions.len()).collect())
+            .collect()
+    }
+}
+
+impl Default for Verifier {
+    fn default() -> Self {
+        Verifier::new(4)
+    }
+}
+
+// Keeps taking the next batch until there are none left. Every batch is returned with its number
+// so the results can be put back in order
+fn validate_batches(
+    transactions: &[&Transaction],
+    next: &AtomicUsize,
+) -> Vec<(usize, Vec<Result<(), BlockchainError>>)> {
+    let mut validated = vec![];
+    loop {
+        let batch = next.fetch_add(1, Ordering::SeqCst);
+        let start = batch * BATCH_SIZE;
+        if start >= transactions.len() {
+            return validated;
+        }
+
+        let end = cmp::min(start + BATCH_SIZE, transactions.len());
+        let results = transactions[start..end]
+            .iter()
+            .map(|tx| tx.validate())
+            .collect();
+        validated.push((batch, results));
+    }
+}
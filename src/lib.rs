@@ -3,6 +3,7 @@ extern crate serde_derive;
 
 extern crate bincode;
 extern crate chrono;
+extern crate crossbeam;
 extern crate hex;
 extern crate itertools;
 extern crate rand;
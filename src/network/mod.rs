@@ -6,6 +6,10 @@ use core;
 pub struct TransferRequest {
     pub id: Uuid,
     pub amount: i64,
+    pub fee: i64,
+    pub nonce: u64,
+    pub valid_after: Option<u64>,
+    pub valid_until: Option<u64>,
     pub sender: String,
     pub recipient: String,
     pub signature: String,
@@ -16,6 +20,16 @@ pub struct BlockIndexResult {
     pub block_index: u64,
 }
 
+#[derive(Serialize)]
+pub struct BlockStatusResult {
+    pub status: core::BlockStatus,
+}
+
+#[derive(Serialize)]
+pub struct MiningStatus {
+    pub progress: Option<core::MiningProgress>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Client {
     pub name: Option<String>,
@@ -25,7 +39,7 @@ pub struct Client {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SelfInformation {
     pub public_key: String,
-    pub blockchain: core::Blockchain,
+    pub blockchain: core::Blockchain<core::Engine>,
 }
 
 #[derive(Serialize)]
@@ -43,6 +57,10 @@ impl From<TransferRequest> for core::Transfer {
         Self {
             id: transfer.id,
             amount: transfer.amount,
+            fee: transfer.fee,
+            nonce: transfer.nonce,
+            valid_after: transfer.valid_after,
+            valid_until: transfer.valid_until,
             sender: transfer.sender,
             recipient: transfer.recipient,
             signature: transfer.signature,
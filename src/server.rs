@@ -15,45 +15,154 @@ extern crate untrusted;
 extern crate iridium;
 
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::env;
+use std::mem;
+use std::sync::{Arc, RwLock};
 
-use hex::ToHex;
+use hex::{FromHex, ToHex};
 use ring::{rand, signature};
+use ring::rand::SecureRandom;
 use rocket::State;
 use rocket_contrib::Json;
 
-use iridium::core::{self, Block, Blockchain, BlockchainError, Transaction};
-use iridium::network::{self, BlockIndexResult, Client, ClientList, ErrorResult, SelfInformation};
+use iridium::core::{self, Block, BlockHeader, BlockStatus, Blockchain, BlockchainError,
+                    ChainErrorKind, ChainSpec, Consensus, Engine, Mempool, Miner, MiningJob,
+                    ProofOfAuthority, ProofOfStake, ProofOfWork, Transaction, Verifier};
+use iridium::network::{self, BlockIndexResult, BlockStatusResult, Client, ClientList, ErrorResult,
+                       MiningStatus, SelfInformation};
+
+// The number of mining threads can be set through this environment variable
+const MINER_THREADS_VAR: &str = "IRIDIUM_MINER_THREADS";
+
+// The number of threads that verify signatures when validating a whole chain can be set through
+// this environment variable
+const VERIFIER_THREADS_VAR: &str = "IRIDIUM_VERIFIER_THREADS";
+
+// The size limit of the mempool in bytes can be set through this environment variable
+const MEMPOOL_BYTES_VAR: &str = "IRIDIUM_MEMPOOL_BYTES";
+
+// The path of the chain spec file the node starts from. The default spec is used if it isn't set
+const CHAIN_SPEC_VAR: &str = "IRIDIUM_CHAIN_SPEC";
+
+// The consensus engine the node follows: `pow` (the default), `poa` or `pos`
+const CONSENSUS_VAR: &str = "IRIDIUM_CONSENSUS";
+
+// The comma separated public keys of the authorities under proof of authority
+const AUTHORITIES_VAR: &str = "IRIDIUM_AUTHORITIES";
+
+// The public key of the validator that creates blocks under proof of stake while nobody has any
+// stake locked
+const BOOTSTRAP_VALIDATOR_VAR: &str = "IRIDIUM_BOOTSTRAP_VALIDATOR";
+
+// The hex encoded 32 byte seed of the node's key pair. A random seed is used if it isn't set,
+// which is fine under proof of work but means the node can't be an authority
+const KEY_SEED_VAR: &str = "IRIDIUM_KEY_SEED";
 
 struct App {
     key_pair: signature::Ed25519KeyPair,
     public_key: String,
-    blockchain: RwLock<Blockchain>,
+    blockchain: RwLock<Blockchain<Engine>>,
     clients: RwLock<HashMap<String, Client>>,
+    mining_job: RwLock<Option<Arc<MiningJob>>>,
 }
 
 impl App {
     pub fn new() -> Self {
-        let rng = rand::SystemRandom::new();
-        let pkcs8_bytes =
-            signature::Ed25519KeyPair::generate_pkcs8(&rng).expect("Cannot create pk/sk pair");
-        let key_pair = signature::Ed25519KeyPair::from_pkcs8(untrusted::Input::from(&pkcs8_bytes))
-            .expect("Cannot create pk/sk pair");
+        let seed = App::key_seed();
+        let key_pair = key_pair(&seed);
         let public_key = key_pair.public_key_bytes().to_hex();
+        let miner = env::var(MINER_THREADS_VAR)
+            .ok()
+            .and_then(|threads| threads.parse().ok())
+            .map_or_else(Miner::default, Miner::new);
+        let spec = env::var(CHAIN_SPEC_VAR)
+            .ok()
+            .map_or_else(ChainSpec::default, |path| {
+                ChainSpec::from_file(path).expect("Cannot load the chain spec")
+            });
+        let consensus = App::consensus(&spec, miner, &seed);
+        let mut blockchain = core::Blockchain::with_spec(spec, consensus);
+        blockchain.mempool = env::var(MEMPOOL_BYTES_VAR)
+            .ok()
+            .and_then(|bytes| bytes.parse().ok())
+            .map_or_else(Mempool::default, Mempool::new);
+        blockchain.verifier = env::var(VERIFIER_THREADS_VAR)
+            .ok()
+            .and_then(|threads| threads.parse().ok())
+            .map_or_else(Verifier::default, Verifier::new);
         Self {
             key_pair,
             public_key,
-            blockchain: RwLock::new(core::Blockchain::new()),
+            blockchain: RwLock::new(blockchain),
             clients: RwLock::new(HashMap::new()),
+            mining_job: RwLock::new(None),
+        }
+    }
+
+    // The seed of our key pair, from the environment if it's set
+    fn key_seed() -> Vec<u8> {
+        match env::var(KEY_SEED_VAR) {
+            Ok(seed) => Vec::from_hex(seed).expect("The key seed isn't valid hex"),
+            Err(_) => {
+                let mut seed = vec![0; 32];
+                rand::SystemRandom::new()
+                    .fill(&mut seed)
+                    .expect("Cannot create pk/sk pair");
+                seed
+            }
+        }
+    }
+
+    // The consensus engine named in the environment. Under proof of authority and proof of stake
+    // we sign blocks with our key pair (a copy of it, since key pairs can't be cloned)
+    fn consensus(spec: &ChainSpec, miner: Miner, seed: &[u8]) -> Engine {
+        match env::var(CONSENSUS_VAR).as_ref().map(String::as_str) {
+            Ok("poa") => {
+                let authorities = env::var(AUTHORITIES_VAR)
+                    .expect("Proof of authority needs the list of authorities")
+                    .split(',')
+                    .map(|authority| authority.trim().to_owned())
+                    .collect();
+                ProofOfAuthority::with_signer(authorities, key_pair(seed)).into()
+            }
+            Ok("pos") => {
+                let bootstrap = env::var(BOOTSTRAP_VALIDATOR_VAR)
+                    .expect("Proof of stake needs the bootstrap validator");
+                ProofOfStake::with_signer(bootstrap, key_pair(seed)).into()
+            }
+            Ok("pow") | Err(_) => ProofOfWork::new(spec.params.clone(), miner).into(),
+            Ok(engine) => panic!("Unknown consensus engine {}", engine),
+        }
+    }
+
+    // Registers a new mining job, cancelling the previous one if it's still running
+    pub fn start_mining(&self) -> Arc<MiningJob> {
+        let job = Arc::new(MiningJob::new());
+        let mut mining_job = self.mining_job.write().unwrap();
+        if let Some(previous) = mem::replace(&mut *mining_job, Some(job.clone())) {
+            previous.cancel();
+        }
+        job
+    }
+
+    // Whatever we're mining is useless once the tip changes, since it builds on the old one
+    pub fn cancel_mining(&self) {
+        if let Some(ref job) = *self.mining_job.read().unwrap() {
+            job.cancel();
         }
     }
 }
 
+fn key_pair(seed: &[u8]) -> signature::Ed25519KeyPair {
+    signature::Ed25519KeyPair::from_seed_unchecked(untrusted::Input::from(seed))
+        .expect("The key seed has to be 32 bytes long")
+}
+
 #[post("/transactions/new", data = "<transfer>")]
 fn new_transaction(
     transfer: Json<network::TransferRequest>,
     app: State<App>,
-) -> Result<Json<Blockchain>, Json<ErrorResult<BlockchainError>>> {
+) -> Result<Json<Blockchain<Engine>>, Json<ErrorResult<BlockchainError>>> {
     let mut blockchain = app.blockchain.write().unwrap();
     blockchain
         .new_transaction(core::Transfer::from(transfer.into_inner()))
@@ -65,12 +174,15 @@ fn new_transaction(
 fn send_coins(
     client: Json<Client>,
     app: State<App>,
-) -> Result<Json<Blockchain>, Json<ErrorResult<BlockchainError>>> {
+) -> Result<Json<Blockchain<Engine>>, Json<ErrorResult<BlockchainError>>> {
     let mut blockchain = app.blockchain.write().unwrap();
+    let nonce = blockchain.next_nonce(&app.public_key);
     let transfer = Transaction::transfer(
         &app.public_key,
         &client.into_inner().public_key,
         50,
+        0,
+        nonce,
         &app.key_pair,
     );
     blockchain
@@ -80,25 +192,57 @@ fn send_coins(
 }
 
 #[get("/chain")]
-fn chain(app: State<App>) -> Json<Blockchain> {
+fn chain(app: State<App>) -> Json<Blockchain<Engine>> {
     let blockchain = app.blockchain.read().unwrap();
     Json(blockchain.clone())
 }
 
+#[get("/headers")]
+fn headers(app: State<App>) -> Result<Json<Vec<BlockHeader>>, Json<ErrorResult<BlockchainError>>> {
+    let blockchain = app.blockchain.read().unwrap();
+    blockchain
+        .headers()
+        .map(|headers| Json(headers.headers().to_vec()))
+        .map_err(|error| Json(ErrorResult { error }))
+}
+
 #[post("/chain/replace", data = "<chain>")]
 fn replace_chain(
-    chain: Json<Blockchain>,
+    chain: Json<Blockchain<Engine>>,
     app: State<App>,
-) -> Result<Json<Blockchain>, Json<ErrorResult<BlockchainError>>> {
+) -> Result<Json<Blockchain<Engine>>, Json<ErrorResult<BlockchainError>>> {
     let mut blockchain = app.blockchain.write().unwrap();
+    let tip = blockchain.last_block().hash.clone();
     blockchain
         .replace(chain.into_inner())
-        .map(|replaced| Json(replaced.clone()))
+        .map(|replaced| {
+            if replaced.last_block().hash != tip {
+                app.cancel_mining();
+            }
+            Json(replaced.clone())
+        })
+        .map_err(|error| Json(ErrorResult { error }))
+}
+
+#[post("/blocks", data = "<block>")]
+fn add_block(
+    block: Json<Block>,
+    app: State<App>,
+) -> Result<Json<BlockStatusResult>, Json<ErrorResult<BlockchainError>>> {
+    let mut blockchain = app.blockchain.write().unwrap();
+    blockchain
+        .add_block(block.into_inner())
+        .map(|status| {
+            if status == BlockStatus::Extended || status == BlockStatus::Reorganized {
+                app.cancel_mining();
+            }
+            Json(BlockStatusResult { status })
+        })
         .map_err(|error| Json(ErrorResult { error }))
 }
 
 #[post("/chain/tamper", data = "<block>")]
-fn tamper(block: Json<Block>, app: State<App>) -> Json<Blockchain> {
+fn tamper(block: Json<Block>, app: State<App>) -> Json<Blockchain<Engine>> {
     let mut blockchain = app.blockchain.write().unwrap();
     blockchain.tamper(block.into_inner());
     Json(blockchain.clone())
@@ -129,11 +273,40 @@ fn clients(app: State<App>) -> Json<ClientList> {
 }
 
 #[post("/mine")]
-fn mine(app: State<App>) -> Json<Block> {
+fn mine(app: State<App>) -> Result<Json<Block>, Json<ErrorResult<BlockchainError>>> {
+    // The chain is only locked while creating the template and adding the result, so the node
+    // keeps serving requests (and accepting new tips, which cancel the search) while mining. The
+    // job is registered before the lock is released, otherwise a tip arriving in between wouldn't
+    // cancel it
+    let (template, consensus, job) = {
+        let blockchain = app.blockchain.read().unwrap();
+        let template = blockchain
+            .block_template(&app.public_key)
+            .map_err(|error| Json(ErrorResult { error }))?;
+        (template, blockchain.consensus.clone(), app.start_mining())
+    };
+    let block = consensus
+        .seal(template, &job)
+        .map_err(|error| Json(ErrorResult { error }))?;
+
+    // The tip may have moved on while we were sealing, in which case the block is only useful if
+    // it still ended up at the tip of the best chain
     let mut blockchain = app.blockchain.write().unwrap();
-    let public_key = &app.public_key;
-    let block = blockchain.mine(public_key);
-    Json(block.clone())
+    match blockchain.add_block(block.clone()) {
+        Ok(BlockStatus::Extended) | Ok(BlockStatus::Reorganized) => Ok(Json(block)),
+        Ok(_) => Err(Json(ErrorResult {
+            error: BlockchainError::chain(ChainErrorKind::StaleBlock),
+        })),
+        Err(error) => Err(Json(ErrorResult { error })),
+    }
+}
+
+#[get("/mine/status")]
+fn mining_status(app: State<App>) -> Json<MiningStatus> {
+    let mining_job = app.mining_job.read().unwrap();
+    Json(MiningStatus {
+        progress: mining_job.as_ref().map(|job| job.progress()),
+    })
 }
 
 fn main() {
@@ -146,9 +319,12 @@ fn main() {
                 new_transaction,
                 send_coins,
                 chain,
+                headers,
                 replace_chain,
+                add_block,
                 tamper,
                 mine,
+                mining_status,
                 register_client,
                 clients,
                 me